@@ -1,39 +1,19 @@
 use chess::*;
 
-fn print_board(board: &Board) {
-    println!("  ┌─────────────────┐");
-    for i in 0..8 {
-        print!("{} │ ", 8 - i);
-        for j in 0..8 {
-            match &board[(7 - i) * 8 + j] {
-                Some(piece) => {
-                    let color_offset = if piece.color == Color::White { 0 } else { 32 };
-                    match piece.piece_type {
-                        PieceType::King => print!("{} ", (b'K' + color_offset) as char),
-                        PieceType::Queen => print!("{} ", (b'Q' + color_offset) as char),
-                        PieceType::Rook => print!("{} ", (b'R' + color_offset) as char),
-                        PieceType::Bishop => print!("{} ", (b'B' + color_offset) as char),
-                        PieceType::Knight => print!("{} ", (b'N' + color_offset) as char),
-                        PieceType::Pawn => print!("{} ", (b'P' + color_offset) as char),
-                    }
-                }
-                None => print!(". "),
-            }
-        }
-        println!("│");
-    }
-    println!("  └─────────────────┘");
-    println!("    a b c d e f g h");
-}
-
 fn main() {
     let mut chess = Chess::new();
+    let renderer = BoardRenderer {
+        style: PieceStyle::Unicode,
+        highlight_last_move: true,
+        highlight_check: true,
+        ..Default::default()
+    };
     //let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R3K3 w").unwrap();
 
     loop {
         println!();
-        print_board(&chess.board);
-        println!("\nTurn: {:?}, Status: {:?}", chess.turn, chess.status);
+        print!("{}", renderer.render(&chess));
+        println!("\nTurn: {:?}, Status: {:?}", chess.side_to_move(), chess.status());
         println!("Enter move (e.g. 'a2 a3'): ");
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
@@ -54,7 +34,7 @@ fn main() {
         match validation_res {
             ValidationResult::Valid(status) => {
                 if let Status::Checkmate(_) = status {
-                    println!("Checkmate! {:?} wins", chess.winner.unwrap());
+                    println!("Checkmate! {:?} wins", chess.winner().unwrap());
                     break;
                 } else if let Status::Draw(d) = status {
                     println!("Draw! {:?}", d);
@@ -0,0 +1,270 @@
+use crate::game_tree::{GameTree, NodeId};
+use crate::search::{same_move, SearchLimits, SearchResult, Searcher};
+use crate::{Chess, Color, Move};
+
+/// Scores are capped to this many centipawns, so a missed mate doesn't count more than a lost game
+const SCORE_CAP: i32 = 1000;
+
+pub const NAG_GOOD: u8 = 1;
+pub const NAG_MISTAKE: u8 = 2;
+pub const NAG_BLUNDER: u8 = 4;
+pub const NAG_INACCURACY: u8 = 6;
+
+/// What the search thought of a move of the main line
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveAnalysis {
+    /// The node reached by the move
+    pub node: NodeId,
+    pub color: Color,
+    /// The best score before the move, in centipawns for the side that moved
+    pub best_score: i32,
+    /// The score after the move, in centipawns for the side that moved
+    pub score: i32,
+    /// How many centipawns worse the move was than the best move, never negative
+    pub loss: i32,
+    /// From 0 to 100, how much of the winning chances the move kept
+    pub accuracy: f64,
+    pub nag: Option<u8>,
+    /// The line the search preferred, empty if the move was the best
+    pub best_line: Vec<Move>,
+}
+
+/// How well one side played over the game
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerSummary {
+    pub moves: usize,
+    pub average_loss: f64,
+    /// The average accuracy of the moves, from 0 to 100
+    pub accuracy: f64,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Annotation {
+    pub moves: Vec<MoveAnalysis>,
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+impl Annotation {
+    pub fn player(&self, color: Color) -> &PlayerSummary {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+}
+
+/// Reviews games by searching every position of the main line
+#[derive(Clone, Debug)]
+pub struct Annotator {
+    pub limits: SearchLimits,
+    /// The least centipawn loss for `?!`
+    pub inaccuracy: i32,
+    /// The least centipawn loss for `?`
+    pub mistake: i32,
+    /// The least centipawn loss for `??`
+    pub blunder: i32,
+    searcher: Searcher,
+}
+
+impl Default for Annotator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The chance of winning from 0 to 100 for a score in centipawns, fitted to games between strong players
+fn win_chance(score: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * score as f64).exp()) - 1.0)
+}
+
+fn move_accuracy(best_score: i32, score: i32) -> f64 {
+    let lost = (win_chance(best_score) - win_chance(score)).max(0.0);
+    (103.1668 * (-0.04354 * lost).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+impl Annotator {
+    pub fn new() -> Self {
+        Annotator {
+            limits: SearchLimits::default(),
+            inaccuracy: 50,
+            mistake: 100,
+            blunder: 300,
+            searcher: Searcher::new(),
+        }
+    }
+
+    /// Searches every move of the main line and the best move before it
+    pub fn analyse(&mut self, tree: &GameTree) -> Annotation {
+        self.searcher.clear();
+        let mut nodes = vec![tree.root()];
+        nodes.extend(tree.main_line());
+        let results: Vec<SearchResult> = nodes
+            .iter()
+            .map(|&id| {
+                let position = tree.node(id).unwrap().position();
+                self.searcher.search(position, &self.limits)
+            })
+            .collect();
+
+        let mut annotation = Annotation::default();
+        for i in 1..nodes.len() {
+            let before = tree.node(nodes[i - 1]).unwrap().position();
+            let mov = tree.node(nodes[i]).unwrap().mov().unwrap();
+            let is_best = results[i - 1]
+                .best_move
+                .as_ref()
+                .is_some_and(|best| same_move(best, mov));
+            let best_score = results[i - 1].score.clamp(-SCORE_CAP, SCORE_CAP);
+            // The move is searched one ply less than the position before it, so
+            // both scores look equally far ahead
+            let score = if is_best {
+                best_score
+            } else {
+                let position = tree.node(nodes[i]).unwrap().position();
+                (-self.searcher.search(position, &self.child_limits()).score)
+                    .clamp(-SCORE_CAP, SCORE_CAP)
+            };
+            let loss = (best_score - score).max(0);
+            // The score before the last move, for the side to move now
+            let earlier = match i {
+                1 => best_score,
+                _ => (-results[i - 2].score).clamp(-SCORE_CAP, SCORE_CAP),
+            };
+
+            let nag = if loss >= self.blunder {
+                Some(NAG_BLUNDER)
+            } else if loss >= self.mistake {
+                Some(NAG_MISTAKE)
+            } else if loss >= self.inaccuracy {
+                Some(NAG_INACCURACY)
+            } else if is_best
+                && best_score - earlier >= self.mistake
+                && self.is_only_good_move(before, mov, best_score)
+            {
+                // The only move punishing the last move of the opponent
+                Some(NAG_GOOD)
+            } else {
+                None
+            };
+
+            annotation.moves.push(MoveAnalysis {
+                node: nodes[i],
+                color: before.side_to_move(),
+                best_score,
+                score,
+                loss,
+                accuracy: move_accuracy(best_score, score),
+                nag,
+                best_line: if is_best {
+                    Vec::new()
+                } else {
+                    results[i - 1].pv.clone()
+                },
+            });
+        }
+
+        annotation.white = summary(&annotation.moves, Color::White);
+        annotation.black = summary(&annotation.moves, Color::Black);
+        annotation
+    }
+
+    /// Analyses the game and writes the result into it: NAGs on the moves, and the
+    /// better line as a variation with a comment after inaccuracies, mistakes and blunders.
+    /// `GameTree::to_pgn` then gives the annotated game.
+    pub fn annotate(&mut self, tree: &mut GameTree) -> Annotation {
+        let annotation = self.analyse(tree);
+
+        for analysis in annotation.moves.iter() {
+            let Some(nag) = analysis.nag else {
+                continue;
+            };
+            tree.add_nag(analysis.node, nag);
+            if nag == NAG_GOOD || analysis.best_line.is_empty() {
+                continue;
+            }
+
+            let parent = tree.node(analysis.node).unwrap().parent().unwrap();
+            let before = tree.node(parent).unwrap().position();
+            let label = match nag {
+                NAG_BLUNDER => "Blunder",
+                NAG_MISTAKE => "Mistake",
+                _ => "Inaccuracy",
+            };
+            let comment = format!(
+                "{}. {} was best.",
+                label,
+                before.to_san(&analysis.best_line[0])
+            );
+            let old = tree.node(analysis.node).unwrap().comment();
+            let comment = if old.is_empty() {
+                comment
+            } else {
+                format!("{} {}", old, comment)
+            };
+            tree.set_comment(analysis.node, &comment);
+
+            let mut id = parent;
+            for mov in analysis.best_line.iter() {
+                match tree.add_move(id, mov) {
+                    Ok(next) => id = next,
+                    Err(_) => break,
+                }
+            }
+        }
+
+        annotation
+    }
+
+    fn child_limits(&self) -> SearchLimits {
+        SearchLimits {
+            depth: self.limits.depth.saturating_sub(1).max(1),
+            ..self.limits
+        }
+    }
+
+    fn is_only_good_move(&mut self, chess: &Chess, best: &Move, best_score: i32) -> bool {
+        let limits = self.child_limits();
+        chess
+            .all_valid_moves()
+            .iter()
+            .filter(|mov| !same_move(mov, best))
+            .all(|mov| {
+                let mut child = chess.clone();
+                child.play(mov);
+                let score =
+                    (-self.searcher.search(&child, &limits).score).clamp(-SCORE_CAP, SCORE_CAP);
+                best_score - score >= self.mistake
+            })
+    }
+}
+
+fn summary(moves: &[MoveAnalysis], color: Color) -> PlayerSummary {
+    let moves: Vec<&MoveAnalysis> = moves.iter().filter(|m| m.color == color).collect();
+    if moves.is_empty() {
+        return PlayerSummary::default();
+    }
+
+    let count = |nag| moves.iter().filter(|m| m.nag == Some(nag)).count();
+    PlayerSummary {
+        moves: moves.len(),
+        average_loss: moves.iter().map(|m| m.loss as f64).sum::<f64>() / moves.len() as f64,
+        accuracy: moves.iter().map(|m| m.accuracy).sum::<f64>() / moves.len() as f64,
+        inaccuracies: count(NAG_INACCURACY),
+        mistakes: count(NAG_MISTAKE),
+        blunders: count(NAG_BLUNDER),
+    }
+}
+
+/// Annotates the game with the default settings and gives it back as PGN
+pub fn annotate_pgn(tree: &GameTree) -> String {
+    let mut tree = tree.clone();
+    Annotator::new().annotate(&mut tree);
+    tree.to_pgn()
+}
@@ -0,0 +1,4 @@
+// A UCI engine using the search in the crate, for chess GUIs like Arena or Cute Chess
+fn main() {
+    chess::uci::run(std::io::stdin().lock(), std::io::stdout());
+}
@@ -0,0 +1,4 @@
+// The same engine as chess-uci, for GUIs speaking the XBoard protocol
+fn main() {
+    chess::xboard::run(std::io::stdin().lock(), std::io::stdout());
+}
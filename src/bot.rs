@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::eval::evaluate;
+use crate::polyglot::Book;
+use crate::search::{SearchLimits, Searcher};
+use crate::{Chess, Move};
+
+/// Named strengths for the bot. The rating bands are rough guesses
+/// for club play, not measured against rated players.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SkillLevel {
+    Beginner,
+    Novice,
+    Intermediate,
+    Advanced,
+    Expert,
+    Master,
+}
+
+impl SkillLevel {
+    pub const ALL: [SkillLevel; 6] = [
+        SkillLevel::Beginner,
+        SkillLevel::Novice,
+        SkillLevel::Intermediate,
+        SkillLevel::Advanced,
+        SkillLevel::Expert,
+        SkillLevel::Master,
+    ];
+
+    /// The rating range the level is meant to play like
+    pub fn rating_band(&self) -> (u16, u16) {
+        match self {
+            SkillLevel::Beginner => (400, 800),
+            SkillLevel::Novice => (800, 1100),
+            SkillLevel::Intermediate => (1100, 1400),
+            SkillLevel::Advanced => (1400, 1700),
+            SkillLevel::Expert => (1700, 2000),
+            SkillLevel::Master => (2000, 2300),
+        }
+    }
+
+    pub fn settings(&self) -> BotSettings {
+        let (depth, eval_noise, mistake_chance, max_mistake, seconds) = match self {
+            SkillLevel::Beginner => (1, 120, 0.3, 400, 1),
+            SkillLevel::Novice => (2, 80, 0.2, 250, 1),
+            SkillLevel::Intermediate => (2, 40, 0.1, 120, 2),
+            SkillLevel::Advanced => (3, 20, 0.05, 60, 3),
+            SkillLevel::Expert => (4, 0, 0.0, 0, 5),
+            SkillLevel::Master => (6, 0, 0.0, 0, 10),
+        };
+        BotSettings {
+            depth,
+            eval_noise,
+            mistake_chance,
+            max_mistake,
+            time: Some(Duration::from_secs(seconds)),
+        }
+    }
+}
+
+/// How the bot plays, usually taken from a `SkillLevel`
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BotSettings {
+    pub depth: u8,
+    /// The most centipawns added to or taken from the score of every move
+    pub eval_noise: i32,
+    /// How often, from 0 to 1, the bot picks a random move that is worse than the best
+    pub mistake_chance: f64,
+    /// How many centipawns worse than the best such a mistake can be
+    pub max_mistake: i32,
+    /// Thinking time for a move, `None` to only stop at the depth
+    pub time: Option<Duration>,
+}
+
+impl Default for BotSettings {
+    fn default() -> Self {
+        SkillLevel::Intermediate.settings()
+    }
+}
+
+/// A computer opponent of adjustable strength
+#[derive(Clone, Debug)]
+pub struct Bot {
+    pub settings: BotSettings,
+    /// Book moves are played while there are any, picked by their weights
+    pub book: Option<Book>,
+    searcher: Searcher,
+    rng: u64,
+}
+
+impl Bot {
+    pub fn new(level: SkillLevel) -> Self {
+        Bot::with_settings(level.settings())
+    }
+
+    pub fn with_settings(settings: BotSettings) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Bot {
+            settings,
+            book: None,
+            searcher: Searcher::new(),
+            rng: 1,
+        }
+        .with_seed(seed)
+    }
+
+    /// Makes the random choices repeatable, e.g. for tests
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        // Xorshift needs a state that is not zero
+        self.rng = seed | 1;
+        self
+    }
+
+    /// Call between games so nothing is remembered from the last one
+    pub fn new_game(&mut self) {
+        self.searcher.clear();
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    // A number from 0 up to but not including 1
+    fn random_f64(&mut self) -> f64 {
+        (self.next_random() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Picks a move for the side to move, `None` if the game is over
+    pub fn choose_move(&mut self, chess: &Chess) -> Option<Move> {
+        let start = Instant::now();
+        let moves = chess.all_valid_moves();
+        if moves.is_empty() {
+            return None;
+        }
+
+        let random = self.next_random();
+        if let Some(mov) = self
+            .book
+            .as_ref()
+            .and_then(|b| b.weighted_move(chess, random))
+        {
+            return Some(mov);
+        }
+
+        // Every move gets its own search so the scores can be compared
+        let time_per_move = self.settings.time.map(|t| t / moves.len() as u32);
+        let mut scored = Vec::new();
+        for mov in moves.into_iter() {
+            let mut child = chess.clone();
+            child.play(&mov);
+
+            let score = if self.settings.depth <= 1
+                || self.settings.time.is_some_and(|t| start.elapsed() >= t)
+            {
+                -evaluate(&child)
+            } else {
+                let limits = SearchLimits {
+                    depth: self.settings.depth - 1,
+                    nodes: None,
+                    time: time_per_move,
+                };
+                -self.searcher.search(&child, &limits).score
+            };
+
+            let noise = if self.settings.eval_noise > 0 {
+                let range = 2 * self.settings.eval_noise as u64 + 1;
+                (self.next_random() % range) as i32 - self.settings.eval_noise
+            } else {
+                0
+            };
+            scored.push((score + noise, mov));
+        }
+
+        scored.sort_by_key(|(score, _)| -score);
+        let best_score = scored[0].0;
+
+        if self.random_f64() < self.settings.mistake_chance {
+            let worse: Vec<usize> = (1..scored.len())
+                .filter(|i| best_score - scored[*i].0 <= self.settings.max_mistake)
+                .collect();
+            if !worse.is_empty() {
+                let i = worse[(self.next_random() % worse.len() as u64) as usize];
+                return Some(scored.swap_remove(i).1);
+            }
+        }
+
+        Some(scored.swap_remove(0).1)
+    }
+}
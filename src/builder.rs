@@ -0,0 +1,110 @@
+use crate::{Board, CastlingRights, Chess, Color, Piece, PieceType, Position, PositionIssue};
+
+/// Sets up a position piece by piece, for example from a board editor.
+/// Nothing is checked until `build` is called.
+#[derive(Clone, Debug)]
+pub struct BoardBuilder {
+    board: Board,
+    turn: Color,
+    castling_rights: CastlingRights,
+    en_passant: Option<Position>,
+    halfmove_clock: u8,
+    fullmove_number: u16,
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoardBuilder {
+    /// An empty board with white to move
+    pub fn new() -> Self {
+        const ARRAY_REPEAT_VALUE: Option<Piece> = None;
+        BoardBuilder {
+            board: [ARRAY_REPEAT_VALUE; 64],
+            turn: Color::White,
+            castling_rights: CastlingRights::default(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    /// Starts from the current position of a game, the game history is not kept
+    pub fn from_chess(chess: &Chess) -> Self {
+        let mut builder = BoardBuilder::new();
+        for piece in chess.board.iter().flatten() {
+            builder.put(piece.position, piece.piece_type, piece.color);
+        }
+        builder.turn = chess.turn;
+        builder.castling_rights = chess.castling_rights;
+        builder.en_passant = chess.en_passant;
+        builder.halfmove_clock = chess.counter_50_move_rule;
+        builder.fullmove_number = chess.fullmove_number;
+        builder
+    }
+
+    /// Places a piece on the square, replacing whatever stood there
+    pub fn put(&mut self, square: Position, piece_type: PieceType, color: Color) -> &mut Self {
+        self.board[square.to_index()] = Some(Piece::new(piece_type, color, square));
+        self
+    }
+
+    pub fn remove(&mut self, square: Position) -> &mut Self {
+        self.board[square.to_index()] = None;
+        self
+    }
+
+    pub fn clear(&mut self) -> &mut Self {
+        for tile in self.board.iter_mut() {
+            *tile = None;
+        }
+        self
+    }
+
+    pub fn set_turn(&mut self, turn: Color) -> &mut Self {
+        self.turn = turn;
+        self
+    }
+
+    pub fn set_castling(&mut self, castling_rights: CastlingRights) -> &mut Self {
+        self.castling_rights = castling_rights;
+        self
+    }
+
+    pub fn set_en_passant(&mut self, en_passant: Option<Position>) -> &mut Self {
+        self.en_passant = en_passant;
+        self
+    }
+
+    pub fn set_clocks(&mut self, halfmove_clock: u8, fullmove_number: u16) -> &mut Self {
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number.max(1);
+        self
+    }
+
+    pub fn piece_at(&self, square: Position) -> Option<&Piece> {
+        self.board[square.to_index()].as_ref()
+    }
+
+    /// Creates the game, or lists why the position is not legal
+    pub fn build(&self) -> Result<Chess, Vec<PositionIssue>> {
+        let chess = Chess::from_parts(
+            self.board.clone(),
+            self.turn,
+            self.castling_rights,
+            self.en_passant,
+            self.halfmove_clock,
+            self.fullmove_number,
+        );
+
+        let issues = chess.validate_position();
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+
+        Ok(chess)
+    }
+}
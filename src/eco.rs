@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::polyglot::polyglot_key;
+use crate::{Chess, Color, GameTree};
+
+// Code, name and the moves leading to the position. When two lines reach the
+// same position, the first one in the table is used.
+#[rustfmt::skip]
+const OPENINGS: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A00", "Grob Opening", "g4"),
+    ("A00", "Hungarian Opening", "g3"),
+    ("A00", "Van Geet Opening", "Nc3"),
+    ("A00", "Van't Kruijs Opening", "e3"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A03", "Bird's Opening, Dutch Variation", "f4 d5"),
+    ("A04", "Réti Opening", "Nf3"),
+    ("A05", "Réti Opening", "Nf3 Nf6"),
+    ("A06", "Réti Opening", "Nf3 d5"),
+    ("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    ("A09", "Réti Opening", "Nf3 d5 c4"),
+    ("A10", "English Opening", "c4"),
+    ("A13", "English Opening", "c4 e6"),
+    ("A15", "English Opening, Anglo-Indian Defence", "c4 Nf6"),
+    ("A16", "English Opening, Anglo-Indian Defence", "c4 Nf6 Nc3"),
+    ("A20", "English Opening, King's English Variation", "c4 e5"),
+    ("A21", "English Opening, King's English Variation", "c4 e5 Nc3"),
+    ("A22", "English Opening, King's English Variation", "c4 e5 Nc3 Nf6"),
+    ("A25", "English Opening, Closed", "c4 e5 Nc3 Nc6"),
+    ("A28", "English Opening, Four Knights", "c4 e5 Nc3 Nf6 Nf3 Nc6"),
+    ("A30", "English Opening, Symmetrical Variation", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A40", "Englund Gambit", "d4 e5"),
+    ("A40", "Modern Defence", "d4 g6"),
+    ("A41", "Queen's Pawn Game", "d4 d6"),
+    ("A43", "Old Benoni Defence", "d4 c5"),
+    ("A45", "Indian Defence", "d4 Nf6"),
+    ("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+    ("A46", "Indian Defence", "d4 Nf6 Nf3"),
+    ("A48", "Indian Defence, East Indian Defence", "d4 Nf6 Nf3 g6"),
+    ("A50", "Indian Defence", "d4 Nf6 c4"),
+    ("A51", "Budapest Gambit", "d4 Nf6 c4 e5"),
+    ("A52", "Budapest Gambit", "d4 Nf6 c4 e5 dxe5 Ng4"),
+    ("A53", "Old Indian Defence", "d4 Nf6 c4 d6"),
+    ("A56", "Benoni Defence", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+    ("A80", "Dutch Defence", "d4 f5"),
+    ("A84", "Dutch Defence", "d4 f5 c4"),
+    ("B00", "King's Pawn Game", "e4"),
+    ("B00", "Nimzowitsch Defence", "e4 Nc6"),
+    ("B00", "Owen's Defence", "e4 b6"),
+    ("B01", "Scandinavian Defence", "e4 d5"),
+    ("B01", "Scandinavian Defence, Mieses-Kotroc Variation", "e4 d5 exd5 Qxd5"),
+    ("B01", "Scandinavian Defence, Main Line", "e4 d5 exd5 Qxd5 Nc3 Qa5"),
+    ("B01", "Scandinavian Defence, Modern Variation", "e4 d5 exd5 Nf6"),
+    ("B02", "Alekhine's Defence", "e4 Nf6"),
+    ("B03", "Alekhine's Defence", "e4 Nf6 e5 Nd5 d4"),
+    ("B03", "Alekhine's Defence, Four Pawns Attack", "e4 Nf6 e5 Nd5 d4 d6 c4 Nb6 f4"),
+    ("B04", "Alekhine's Defence, Modern Variation", "e4 Nf6 e5 Nd5 d4 d6 Nf3"),
+    ("B06", "Modern Defence", "e4 g6"),
+    ("B07", "Pirc Defence", "e4 d6 d4 Nf6"),
+    ("B07", "Pirc Defence", "e4 d6 d4 Nf6 Nc3 g6"),
+    ("B09", "Pirc Defence, Austrian Attack", "e4 d6 d4 Nf6 Nc3 g6 f4"),
+    ("B10", "Caro-Kann Defence", "e4 c6"),
+    ("B12", "Caro-Kann Defence", "e4 c6 d4 d5"),
+    ("B12", "Caro-Kann Defence, Advance Variation", "e4 c6 d4 d5 e5"),
+    ("B13", "Caro-Kann Defence, Exchange Variation", "e4 c6 d4 d5 exd5 cxd5"),
+    ("B13", "Caro-Kann Defence, Panov Attack", "e4 c6 d4 d5 exd5 cxd5 c4"),
+    ("B15", "Caro-Kann Defence", "e4 c6 d4 d5 Nc3"),
+    ("B15", "Caro-Kann Defence", "e4 c6 d4 d5 Nc3 dxe4 Nxe4"),
+    ("B17", "Caro-Kann Defence, Karpov Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7"),
+    ("B18", "Caro-Kann Defence, Classical Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5"),
+    ("B20", "Sicilian Defence", "e4 c5"),
+    ("B21", "Sicilian Defence, Smith-Morra Gambit", "e4 c5 d4 cxd4 c3"),
+    ("B22", "Sicilian Defence, Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defence, Closed", "e4 c5 Nc3"),
+    ("B27", "Sicilian Defence", "e4 c5 Nf3"),
+    ("B30", "Sicilian Defence, Old Sicilian", "e4 c5 Nf3 Nc6"),
+    ("B30", "Sicilian Defence, Rossolimo Variation", "e4 c5 Nf3 Nc6 Bb5"),
+    ("B32", "Sicilian Defence, Open", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4"),
+    ("B33", "Sicilian Defence, Sveshnikov Variation", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5"),
+    ("B34", "Sicilian Defence, Accelerated Dragon", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6"),
+    ("B40", "Sicilian Defence, French Variation", "e4 c5 Nf3 e6"),
+    ("B41", "Sicilian Defence, Kan Variation", "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6"),
+    ("B44", "Sicilian Defence, Taimanov Variation", "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6"),
+    ("B50", "Sicilian Defence", "e4 c5 Nf3 d6"),
+    ("B51", "Sicilian Defence, Moscow Variation", "e4 c5 Nf3 d6 Bb5+"),
+    ("B54", "Sicilian Defence, Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    ("B56", "Sicilian Defence, Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3"),
+    ("B56", "Sicilian Defence, Classical Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6"),
+    ("B70", "Sicilian Defence, Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6"),
+    ("B75", "Sicilian Defence, Dragon Variation, Yugoslav Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3"),
+    ("B80", "Sicilian Defence, Scheveningen Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6"),
+    ("B90", "Sicilian Defence, Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6"),
+    ("B90", "Sicilian Defence, Najdorf Variation, English Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3"),
+    ("B92", "Sicilian Defence, Najdorf Variation, Opocensky Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be2"),
+    ("B94", "Sicilian Defence, Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5"),
+    ("C00", "French Defence", "e4 e6"),
+    ("C01", "French Defence, Exchange Variation", "e4 e6 d4 d5 exd5"),
+    ("C02", "French Defence, Advance Variation", "e4 e6 d4 d5 e5"),
+    ("C03", "French Defence, Tarrasch Variation", "e4 e6 d4 d5 Nd2"),
+    ("C10", "French Defence", "e4 e6 d4 d5 Nc3"),
+    ("C10", "French Defence, Rubinstein Variation", "e4 e6 d4 d5 Nc3 dxe4"),
+    ("C11", "French Defence, Classical Variation", "e4 e6 d4 d5 Nc3 Nf6"),
+    ("C15", "French Defence, Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C21", "Centre Game", "e4 e5 d4 exd4"),
+    ("C21", "Danish Gambit", "e4 e5 d4 exd4 c3"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C31", "King's Gambit Declined, Falkbeer Countergambit", "e4 e5 f4 d5"),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C40", "Latvian Gambit", "e4 e5 Nf3 f5"),
+    ("C41", "Philidor Defence", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defence", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Knight Opening, Normal Variation", "e4 e5 Nf3 Nc6"),
+    ("C44", "Ponziani Opening", "e4 e5 Nf3 Nc6 c3"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C48", "Four Knights Game, Spanish Variation", "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Italian Game, Hungarian Defence", "e4 e5 Nf3 Nc6 Bc4 Be7"),
+    ("C50", "Italian Game, Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C51", "Italian Game, Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C53", "Italian Game, Classical Variation", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3"),
+    ("C54", "Italian Game, Giuoco Pianissimo", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3 Nf6 d3"),
+    ("C55", "Italian Game, Two Knights Defence", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    ("C57", "Italian Game, Two Knights Defence, Knight Attack", "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5"),
+    ("C57", "Italian Game, Two Knights Defence, Fried Liver Attack", "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 d5 exd5 Nxd5 Nxf7"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C62", "Ruy Lopez, Steinitz Defence", "e4 e5 Nf3 Nc6 Bb5 d6"),
+    ("C63", "Ruy Lopez, Schliemann Defence", "e4 e5 Nf3 Nc6 Bb5 f5"),
+    ("C65", "Ruy Lopez, Berlin Defence", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C67", "Ruy Lopez, Berlin Defence, Berlin Wall", "e4 e5 Nf3 Nc6 Bb5 Nf6 O-O Nxe4 d4 Nd6 Bxc6 dxc6 dxe5 Nf5 Qxd8+ Kxd8"),
+    ("C68", "Ruy Lopez, Exchange Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+    ("C70", "Ruy Lopez, Morphy Defence", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4"),
+    ("C77", "Ruy Lopez, Morphy Defence", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6"),
+    ("C78", "Ruy Lopez, Morphy Defence", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O"),
+    ("C80", "Ruy Lopez, Open Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4"),
+    ("C84", "Ruy Lopez, Closed", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7"),
+    ("C84", "Ruy Lopez, Closed", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3"),
+    ("C89", "Ruy Lopez, Marshall Attack", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5"),
+    ("C92", "Ruy Lopez, Closed", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3"),
+    ("C92", "Ruy Lopez, Closed, Zaitsev System", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Bb7"),
+    ("C94", "Ruy Lopez, Closed, Breyer Defence", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8"),
+    ("C96", "Ruy Lopez, Closed, Chigorin Defence", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Na5 Bc2"),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D00", "Queen's Pawn Game, London System", "d4 d5 Bf4"),
+    ("D00", "Blackmar-Diemer Gambit", "d4 d5 e4"),
+    ("D02", "Queen's Pawn Game", "d4 d5 Nf3"),
+    ("D02", "Queen's Pawn Game, London System", "d4 d5 Nf3 Nf6 Bf4"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D07", "Queen's Gambit Declined, Chigorin Defence", "d4 d5 c4 Nc6"),
+    ("D08", "Queen's Gambit Declined, Albin Countergambit", "d4 d5 c4 e5"),
+    ("D10", "Slav Defence", "d4 d5 c4 c6"),
+    ("D11", "Slav Defence", "d4 d5 c4 c6 Nf3"),
+    ("D15", "Slav Defence", "d4 d5 c4 c6 Nf3 Nf6 Nc3"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D31", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3"),
+    ("D32", "Tarrasch Defence", "d4 d5 c4 e6 Nc3 c5"),
+    ("D35", "Queen's Gambit Declined, Exchange Variation", "d4 d5 c4 e6 Nc3 Nf6 cxd5 exd5"),
+    ("D37", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3 Nf6 Nf3"),
+    ("D43", "Semi-Slav Defence", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6"),
+    ("D45", "Semi-Slav Defence", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 e3"),
+    ("D50", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3 Nf6 Bg5"),
+    ("D80", "Grünfeld Defence", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("D85", "Grünfeld Defence, Exchange Variation", "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5"),
+    ("E00", "Indian Defence", "d4 Nf6 c4 e6"),
+    ("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    ("E10", "Indian Defence", "d4 Nf6 c4 e6 Nf3"),
+    ("E11", "Bogo-Indian Defence", "d4 Nf6 c4 e6 Nf3 Bb4+"),
+    ("E12", "Queen's Indian Defence", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defence", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E32", "Nimzo-Indian Defence, Classical Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Qc2"),
+    ("E40", "Nimzo-Indian Defence, Rubinstein Variation", "d4 Nf6 c4 e6 Nc3 Bb4 e3"),
+    ("E60", "King's Indian Defence", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defence", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    ("E70", "King's Indian Defence", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6"),
+    ("E76", "King's Indian Defence, Four Pawns Attack", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4"),
+    ("E80", "King's Indian Defence, Sämisch Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3"),
+    ("E90", "King's Indian Defence", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3"),
+    ("E92", "King's Indian Defence, Classical Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5"),
+    ("E97", "King's Indian Defence, Mar del Plata Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7"),
+];
+
+/// A named opening from the ECO classification
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Opening {
+    /// The code, from A00 to E99
+    pub eco: String,
+    pub name: String,
+    /// The half move where the opening was last recognised
+    pub ply: usize,
+}
+
+impl std::fmt::Display for Opening {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.eco, self.name)
+    }
+}
+
+// Every position of the table by its hash, so transpositions are found too
+fn positions() -> &'static HashMap<u64, (&'static str, &'static str)> {
+    static POSITIONS: OnceLock<HashMap<u64, (&'static str, &'static str)>> = OnceLock::new();
+    POSITIONS.get_or_init(|| {
+        let mut positions = HashMap::new();
+        for (eco, name, moves) in OPENINGS.iter() {
+            let mut chess = Chess::new();
+            for san in moves.split_whitespace() {
+                let mov = chess
+                    .parse_san(san)
+                    .unwrap_or_else(|| panic!("Invalid move {} in {}", san, eco));
+                chess.play(&mov);
+            }
+            positions
+                .entry(polyglot_key(&chess))
+                .or_insert((*eco, *name));
+        }
+        positions
+    })
+}
+
+/// The opening of the position itself, if it is in the table
+pub fn lookup(chess: &Chess) -> Option<Opening> {
+    let (eco, name) = positions().get(&polyglot_key(chess))?;
+    let turn = (chess.side_to_move() == Color::Black) as usize;
+    Some(Opening {
+        eco: eco.to_string(),
+        name: name.to_string(),
+        ply: (chess.fullmove_number() as usize - 1) * 2 + turn,
+    })
+}
+
+/// The opening of the game's main line, from the last position in the table
+pub fn classify(game: &GameTree) -> Option<Opening> {
+    let mut opening = None;
+    for (i, id) in game.main_line().into_iter().enumerate() {
+        let position = game.node(id).unwrap().position();
+        if let Some((eco, name)) = positions().get(&polyglot_key(position)) {
+            opening = Some(Opening {
+                eco: eco.to_string(),
+                name: name.to_string(),
+                ply: i + 1,
+            });
+        }
+    }
+    opening
+}
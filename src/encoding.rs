@@ -0,0 +1,244 @@
+use crate::{
+    CastlingRights, Chess, Color, Move, Piece, PieceType, Position, Status, ValidationResult,
+};
+
+/// Size of an encoded position: 8 bytes occupancy, 16 bytes of pieces,
+/// turn and castling, en passant, halfmove clock and two bytes fullmove number
+pub const POSITION_BYTES: usize = 29;
+
+const NO_EN_PASSANT: u8 = 8;
+
+/// A move packed into 16 bits: from square in bits 0-5, to square in bits 6-11
+/// and the promotion in bits 12-14 (0 none, 1 knight, 2 bishop, 3 rook, 4 queen)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompactMove(pub u16);
+
+impl CompactMove {
+    pub fn new(from: Position, to: Position, promotion: Option<PieceType>) -> Self {
+        let promotion_bits = match promotion {
+            None => 0,
+            Some(PieceType::Knight) => 1,
+            Some(PieceType::Bishop) => 2,
+            Some(PieceType::Rook) => 3,
+            Some(_) => 4,
+        };
+        CompactMove(from.to_index() as u16 | (to.to_index() as u16) << 6 | promotion_bits << 12)
+    }
+
+    pub fn from_move(mov: &Move) -> Self {
+        CompactMove::new(mov.from, mov.to, mov.promotion)
+    }
+
+    pub fn from(&self) -> Position {
+        Position::from_index((self.0 & 0x3f) as usize)
+    }
+
+    pub fn to(&self) -> Position {
+        Position::from_index((self.0 >> 6 & 0x3f) as usize)
+    }
+
+    /// `None` both for no promotion and for promotion bits that don't mean anything
+    pub fn promotion(&self) -> Option<PieceType> {
+        match self.0 >> 12 & 0x7 {
+            1 => Some(PieceType::Knight),
+            2 => Some(PieceType::Bishop),
+            3 => Some(PieceType::Rook),
+            4 => Some(PieceType::Queen),
+            _ => None,
+        }
+    }
+
+    /// Finds the valid move in the position this encodes, if there is one
+    pub fn to_move(&self, chess: &Chess) -> Option<Move> {
+        chess
+            .all_valid_moves()
+            .into_iter()
+            .find(|m| CompactMove::from_move(m) == *self)
+    }
+}
+
+fn piece_to_nibble(piece: &Piece) -> u8 {
+    let piece_bits = match piece.piece_type {
+        PieceType::King => 0,
+        PieceType::Queen => 1,
+        PieceType::Rook => 2,
+        PieceType::Bishop => 3,
+        PieceType::Knight => 4,
+        PieceType::Pawn => 5,
+    };
+    let color_bit = if piece.color == Color::Black { 8 } else { 0 };
+    piece_bits | color_bit
+}
+
+fn nibble_to_piece(nibble: u8, position: Position) -> Option<Piece> {
+    let piece_type = match nibble & 0x7 {
+        0 => PieceType::King,
+        1 => PieceType::Queen,
+        2 => PieceType::Rook,
+        3 => PieceType::Bishop,
+        4 => PieceType::Knight,
+        5 => PieceType::Pawn,
+        _ => return None,
+    };
+    let color = if nibble & 8 != 0 {
+        Color::Black
+    } else {
+        Color::White
+    };
+    Some(Piece::new(piece_type, color, position))
+}
+
+impl Chess {
+    /// Packs the position into `POSITION_BYTES` bytes. The repetition history is not included.
+    pub fn encode_position(&self) -> Result<[u8; POSITION_BYTES], &'static str> {
+        if self.status == Status::AwaitingPromotion {
+            return Err("Awaiting promotion");
+        }
+
+        let mut bytes = [0; POSITION_BYTES];
+        let mut occupancy: u64 = 0;
+
+        for (count, piece) in self.board.iter().flatten().enumerate() {
+            if count == 32 {
+                return Err("Too many pieces");
+            }
+            occupancy |= 1 << piece.position.to_index();
+            // Two pieces per byte, the first one in the low bits
+            bytes[8 + count / 2] |= piece_to_nibble(piece) << (4 * (count % 2));
+        }
+        bytes[..8].copy_from_slice(&occupancy.to_le_bytes());
+
+        let mut flags = if self.turn == Color::Black { 1 } else { 0 };
+        for (i, castling_type) in CastlingRights::ALL.iter().enumerate() {
+            if self.castling_rights.has(*castling_type) {
+                flags |= 2 << i;
+            }
+        }
+        bytes[24] = flags;
+        bytes[25] = self.en_passant.map_or(NO_EN_PASSANT, |p| p.x as u8);
+        bytes[26] = self.counter_50_move_rule;
+        bytes[27..29].copy_from_slice(&self.fullmove_number.to_le_bytes());
+
+        Ok(bytes)
+    }
+
+    /// Unpacks a position made by `encode_position`, refusing positions that are not legal
+    pub fn decode_position(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() != POSITION_BYTES {
+            return Err("Invalid length");
+        }
+
+        const ARRAY_REPEAT_VALUE: Option<Piece> = None;
+        let mut board = [ARRAY_REPEAT_VALUE; 64];
+        let occupancy = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+
+        let mut count = 0;
+        for (i, tile) in board.iter_mut().enumerate() {
+            if occupancy & (1 << i) == 0 {
+                continue;
+            }
+            let nibble = bytes[8 + count / 2] >> (4 * (count % 2)) & 0xf;
+            *tile = Some(nibble_to_piece(nibble, Position::from_index(i)).ok_or("Invalid piece")?);
+            count += 1;
+        }
+
+        let flags = bytes[24];
+        if flags >> 5 != 0 {
+            return Err("Invalid flags");
+        }
+        let turn = if flags & 1 != 0 {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let mut castling_rights = CastlingRights::default();
+        for (i, castling_type) in CastlingRights::ALL.iter().enumerate() {
+            castling_rights.set(*castling_type, flags & (2 << i) != 0);
+        }
+
+        let en_passant = match bytes[25] {
+            NO_EN_PASSANT => None,
+            x if x < 8 => Some(Position {
+                x: x as usize,
+                y: if turn == Color::White { 5 } else { 2 },
+            }),
+            _ => return Err("Invalid en passant square"),
+        };
+
+        let fullmove_number = u16::from_le_bytes([bytes[27], bytes[28]]);
+        if fullmove_number == 0 {
+            return Err("Invalid fullmove number");
+        }
+
+        let chess = Chess::from_parts(
+            board,
+            turn,
+            castling_rights,
+            en_passant,
+            bytes[26],
+            fullmove_number,
+        );
+        if !chess.validate_position().is_empty() {
+            return Err("Illegal position");
+        }
+
+        Ok(chess)
+    }
+}
+
+/// Stores a game as its starting position followed by one byte per move,
+/// the index of the move in `Chess::all_valid_moves`
+pub fn encode_game(start: &Chess, moves: &[Move]) -> Result<Vec<u8>, &'static str> {
+    let mut bytes = start.encode_position()?.to_vec();
+    if moves.len() > u16::MAX as usize {
+        return Err("Too many moves");
+    }
+    bytes.extend_from_slice(&(moves.len() as u16).to_le_bytes());
+
+    let mut chess = start.clone();
+    for mov in moves.iter() {
+        let compact = CompactMove::from_move(mov);
+        let index = chess
+            .all_valid_moves()
+            .iter()
+            .position(|m| CompactMove::from_move(m) == compact)
+            .ok_or("Illegal move")?;
+        bytes.push(index as u8);
+        if !matches!(chess.play(mov), ValidationResult::Valid(_)) {
+            return Err("Illegal move");
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Replays a game made by `encode_game`, giving back the starting position and the moves
+pub fn decode_game(bytes: &[u8]) -> Result<(Chess, Vec<Move>), &'static str> {
+    if bytes.len() < POSITION_BYTES + 2 {
+        return Err("Invalid length");
+    }
+
+    let start = Chess::decode_position(&bytes[..POSITION_BYTES])?;
+    let count = u16::from_le_bytes([bytes[POSITION_BYTES], bytes[POSITION_BYTES + 1]]) as usize;
+    let indices = &bytes[POSITION_BYTES + 2..];
+    if indices.len() != count {
+        return Err("Invalid length");
+    }
+
+    let mut chess = start.clone();
+    let mut moves = Vec::new();
+    for index in indices.iter() {
+        let mov = chess
+            .all_valid_moves()
+            .into_iter()
+            .nth(*index as usize)
+            .ok_or("Illegal move")?;
+        if !matches!(chess.play(&mov), ValidationResult::Valid(_)) {
+            return Err("Illegal move");
+        }
+        moves.push(mov);
+    }
+
+    Ok((start, moves))
+}
@@ -0,0 +1,431 @@
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::uci::{move_to_uci, parse_uci_move, GoParams};
+use crate::{Chess, Color, Move, ValidationResult};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum EngineError {
+    /// The engine could not be started
+    Spawn(std::io::Error),
+    /// Writing to the engine failed, usually because it has exited
+    Io(std::io::Error),
+    /// The engine didn't answer in time
+    Timeout,
+    /// The engine closed its output
+    Closed,
+    /// A move that is not valid in the position, sent to or from the engine
+    InvalidMove(String),
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EngineError::Spawn(e) => write!(f, "could not start engine: {}", e),
+            EngineError::Io(e) => write!(f, "could not talk to engine: {}", e),
+            EngineError::Timeout => write!(f, "engine timed out"),
+            EngineError::Closed => write!(f, "engine closed"),
+            EngineError::InvalidMove(m) => write!(f, "invalid move {}", m),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// A score from the point of view of the side to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate, negative if the side to move gets mated
+    Mate(i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScoreBound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// An `info` line from the engine
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EngineInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub bound: Option<ScoreBound>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    /// Milliseconds
+    pub time: Option<u64>,
+    /// Only the valid start of the line if the engine sent a move that doesn't fit the position
+    pub pv: Vec<Move>,
+    pub string: Option<String>,
+}
+
+/// Parses an `info` line for the given position, `None` if it's not an info line
+pub fn parse_info(line: &str, position: &Chess) -> Option<EngineInfo> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.first() != Some(&"info") {
+        return None;
+    }
+
+    let mut info = EngineInfo::default();
+    let mut i = 1;
+    while i < words.len() {
+        let next = words.get(i + 1);
+        match words[i] {
+            "depth" => info.depth = next.and_then(|v| v.parse().ok()),
+            "seldepth" => info.seldepth = next.and_then(|v| v.parse().ok()),
+            "multipv" => info.multipv = next.and_then(|v| v.parse().ok()),
+            "nodes" => info.nodes = next.and_then(|v| v.parse().ok()),
+            "nps" => info.nps = next.and_then(|v| v.parse().ok()),
+            "time" => info.time = next.and_then(|v| v.parse().ok()),
+            "score" => {
+                let value = words.get(i + 2).and_then(|v| v.parse().ok());
+                info.score = match (next, value) {
+                    (Some(&"cp"), Some(v)) => Some(Score::Centipawns(v)),
+                    (Some(&"mate"), Some(v)) => Some(Score::Mate(v)),
+                    _ => None,
+                };
+                info.bound = Some(ScoreBound::Exact);
+                i += 3;
+                continue;
+            }
+            "lowerbound" => {
+                info.bound = Some(ScoreBound::Lower);
+                i += 1;
+                continue;
+            }
+            "upperbound" => {
+                info.bound = Some(ScoreBound::Upper);
+                i += 1;
+                continue;
+            }
+            "string" => {
+                // The rest of the line is the string
+                info.string = Some(words[i + 1..].join(" "));
+                break;
+            }
+            "pv" => {
+                let mut chess = position.clone();
+                for s in words[i + 1..].iter() {
+                    match parse_uci_move(&chess, s) {
+                        Some(mov) if matches!(chess.play(&mov), ValidationResult::Valid(_)) => {
+                            info.pv.push(mov)
+                        }
+                        _ => break,
+                    }
+                }
+                // The pv is the last thing on the line
+                break;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+
+    Some(info)
+}
+
+/// A `bestmove` answer, with the last info the engine sent before it
+#[derive(Clone, Debug, PartialEq)]
+pub struct BestMove {
+    /// `None` if the engine had no move, e.g. in a finished game
+    pub best_move: Option<Move>,
+    pub ponder: Option<Move>,
+    pub info: Option<EngineInfo>,
+}
+
+/// An option the engine said it has
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+    /// E.g. `spin`, `check`, `combo`, `button` or `string`
+    pub kind: String,
+    pub default: Option<String>,
+}
+
+fn parse_option(line: &str) -> Option<EngineOption> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.first() != Some(&"option") {
+        return None;
+    }
+    let find = |key: &str| words.iter().position(|w| *w == key);
+    let keywords = ["name", "type", "default", "min", "max", "var"];
+    // A value runs until the next keyword, names can contain spaces
+    let value_of = |key: &str| {
+        find(key).map(|start| {
+            words[start + 1..]
+                .iter()
+                .take_while(|w| !keywords.contains(w))
+                .copied()
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+    };
+
+    Some(EngineOption {
+        name: value_of("name")?,
+        kind: value_of("type").unwrap_or_default(),
+        default: value_of("default"),
+    })
+}
+
+/// A UCI engine running as a child process
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// How long to wait for answers, and for a search longer than its time limit
+    pub timeout: Duration,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub options: Vec<EngineOption>,
+    position: Chess,
+}
+
+impl Engine {
+    /// Starts the engine and waits for it to finish the `uci` handshake
+    pub fn spawn<S: AsRef<OsStr>>(program: S, args: &[&str]) -> Result<Engine, EngineError> {
+        Engine::spawn_with_timeout(program, args, DEFAULT_TIMEOUT)
+    }
+
+    pub fn spawn_with_timeout<S: AsRef<OsStr>>(
+        program: S,
+        args: &[&str],
+        timeout: Duration,
+    ) -> Result<Engine, EngineError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(EngineError::Spawn)?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read on a thread of its own so waiting can time out
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            child,
+            stdin,
+            lines,
+            timeout,
+            name: None,
+            author: None,
+            options: Vec::new(),
+            position: Chess::new(),
+        };
+
+        engine.send("uci")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = engine.read_line(deadline)?;
+            if line.trim() == "uciok" {
+                break;
+            } else if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = Some(name.trim().to_string());
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                engine.author = Some(author.trim().to_string());
+            } else if let Some(option) = parse_option(&line) {
+                engine.options.push(option);
+            }
+        }
+
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{}", command).map_err(EngineError::Io)?;
+        self.stdin.flush().map_err(EngineError::Io)
+    }
+
+    fn read_line(&self, deadline: Instant) -> Result<String, EngineError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => EngineError::Timeout,
+            RecvTimeoutError::Disconnected => EngineError::Closed,
+        })
+    }
+
+    /// Waits until the engine has handled everything sent to it
+    pub fn is_ready(&mut self) -> Result<(), EngineError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + self.timeout;
+        while self.read_line(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), EngineError> {
+        match value {
+            Some(value) => self.send(&format!("setoption name {} value {}", name, value))?,
+            None => self.send(&format!("setoption name {}", name))?,
+        }
+        self.is_ready()
+    }
+
+    pub fn new_game(&mut self) -> Result<(), EngineError> {
+        self.send("ucinewgame")?;
+        self.position = Chess::new();
+        self.is_ready()
+    }
+
+    /// Sends the position as a FEN
+    pub fn set_position(&mut self, chess: &Chess) -> Result<(), EngineError> {
+        self.set_position_with_moves(chess, &[])
+    }
+
+    /// Sends the starting position and the moves played from it, so the engine knows about repetitions
+    pub fn set_position_with_moves(
+        &mut self,
+        start: &Chess,
+        moves: &[Move],
+    ) -> Result<(), EngineError> {
+        let mut command = format!("position fen {}", start.to_fen());
+        let mut chess = start.clone();
+        if !moves.is_empty() {
+            command.push_str(" moves");
+        }
+        for mov in moves.iter() {
+            command.push(' ');
+            command.push_str(&move_to_uci(&chess, mov));
+            if !matches!(chess.play(mov), ValidationResult::Valid(_)) {
+                return Err(EngineError::InvalidMove(mov.to_string()));
+            }
+        }
+
+        self.send(&command)?;
+        self.position = chess;
+        Ok(())
+    }
+
+    /// Starts a search, finish it with `wait_best_move` or `stop`
+    pub fn start_search(&mut self, params: &GoParams) -> Result<(), EngineError> {
+        self.send(&params.to_command())
+    }
+
+    /// Reads the info lines until the best move, calling `on_info` for each one
+    pub fn wait_best_move(
+        &mut self,
+        timeout: Duration,
+        mut on_info: impl FnMut(&EngineInfo),
+    ) -> Result<BestMove, EngineError> {
+        let deadline = Instant::now() + timeout;
+        let mut last_info = None;
+        loop {
+            let line = self.read_line(deadline)?;
+            if let Some(info) = parse_info(&line, &self.position) {
+                on_info(&info);
+                // Lines with only a string or current move don't say anything about the result
+                if info.score.is_some() || !info.pv.is_empty() {
+                    last_info = Some(info);
+                }
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.first() != Some(&"bestmove") {
+                continue;
+            }
+
+            let best_move = match words.get(1) {
+                None | Some(&"0000") | Some(&"(none)") => None,
+                Some(s) => Some(
+                    parse_uci_move(&self.position, s)
+                        .ok_or_else(|| EngineError::InvalidMove(s.to_string()))?,
+                ),
+            };
+            let ponder = match (&best_move, words.get(2), words.get(3)) {
+                (Some(mov), Some(&"ponder"), Some(s)) => {
+                    let mut chess = self.position.clone();
+                    chess.play(mov);
+                    parse_uci_move(&chess, s)
+                }
+                _ => None,
+            };
+
+            return Ok(BestMove {
+                best_move,
+                ponder,
+                info: last_info,
+            });
+        }
+    }
+
+    /// Searches the position and waits for the result. A search running past its
+    /// `movetime`, or the clock of the side to move without one, by more than `timeout`
+    /// is stopped, and it times out if even that fails.
+    pub fn go(&mut self, params: &GoParams) -> Result<BestMove, EngineError> {
+        self.start_search(params)?;
+        // The engine shares out its own clock, but can never use more than all of it
+        let clock = match self.position.side_to_move() {
+            Color::White => params.wtime,
+            Color::Black => params.btime,
+        };
+        let time = params.movetime.or(clock).unwrap_or(0);
+        let limit = Duration::from_millis(time) + self.timeout;
+        match self.wait_best_move(limit, |_| {}) {
+            Err(EngineError::Timeout) => self.stop(),
+            res => res,
+        }
+    }
+
+    /// Stops the running search and gives back its result
+    pub fn stop(&mut self) -> Result<BestMove, EngineError> {
+        self.send("stop")?;
+        self.wait_best_move(self.timeout, |_| {})
+    }
+
+    /// Asks the engine to exit, and kills it if it doesn't in time
+    pub fn quit(mut self) -> Result<(), EngineError> {
+        // The engine might already be gone
+        let _ = self.send("quit");
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        Err(EngineError::Timeout)
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
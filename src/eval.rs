@@ -0,0 +1,293 @@
+use crate::moves::{find_king, generate_moves, is_square_attacked};
+use crate::pawns::PawnStructure;
+use crate::{Board, Chess, Color, PieceType, Position, Status};
+
+/// The score of a checkmate, far above anything the material can add up to
+pub const MATE_SCORE: i32 = 100_000;
+
+/// The phase when all pieces are on the board, see `EvalTerms::phase`
+pub const MAX_PHASE: i32 = 24;
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+    }
+}
+
+fn phase_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        _ => 0,
+    }
+}
+
+// Piece-square tables from white's point of view, with rank 8 on the first row
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// Middlegame and endgame values for a piece on a square
+fn piece_square(piece_type: PieceType, color: Color, square: Position) -> (i32, i32) {
+    let rank = if color == Color::White {
+        7 - square.y
+    } else {
+        square.y
+    };
+    let i = rank * 8 + square.x;
+    match piece_type {
+        PieceType::Pawn => (PAWN_MG[i], PAWN_EG[i]),
+        PieceType::Knight => (KNIGHT[i], KNIGHT[i]),
+        PieceType::Bishop => (BISHOP[i], BISHOP[i]),
+        PieceType::Rook => (ROOK[i], ROOK[i]),
+        PieceType::Queen => (QUEEN[i], QUEEN[i]),
+        PieceType::King => (KING_MG[i], KING_EG[i]),
+    }
+}
+
+fn mobility_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight => 4,
+        PieceType::Bishop => 5,
+        PieceType::Rook => 2,
+        PieceType::Queen => 1,
+        _ => 0,
+    }
+}
+
+const DOUBLED_PAWN: i32 = -15;
+const ISOLATED_PAWN: i32 = -12;
+const BACKWARD_PAWN: i32 = -8;
+// Bonus for a passed pawn by how far it has advanced
+const PASSED_PAWN: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+/// The parts of the evaluation, in centipawns from white's point of view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalTerms {
+    pub material: i32,
+    /// Tapered between the middlegame and endgame tables by `phase`
+    pub piece_squares: i32,
+    pub mobility: i32,
+    pub king_safety: i32,
+    pub pawn_structure: i32,
+    /// How much of the pieces are left, from `MAX_PHASE` at the start to 0 with only kings and pawns
+    pub phase: i32,
+}
+
+impl EvalTerms {
+    pub fn total(&self) -> i32 {
+        self.material + self.piece_squares + self.mobility + self.king_safety + self.pawn_structure
+    }
+}
+
+fn sign(color: Color) -> i32 {
+    color as i32
+}
+
+/// Evaluates the position in centipawns from the point of view of the side to move
+pub fn evaluate(chess: &Chess) -> i32 {
+    match chess.status() {
+        Status::Checkmate(color) if color == chess.side_to_move() => return -MATE_SCORE,
+        Status::Checkmate(_) => return MATE_SCORE,
+        Status::Draw(_) => return 0,
+        _ => {}
+    }
+
+    evaluate_terms(chess).total() * sign(chess.side_to_move())
+}
+
+/// Every term of the evaluation separately, to explain a score
+pub fn evaluate_terms(chess: &Chess) -> EvalTerms {
+    let board = chess.board();
+
+    let phase = board
+        .iter()
+        .flatten()
+        .map(|p| phase_weight(p.piece_type))
+        .sum::<i32>()
+        .min(MAX_PHASE);
+
+    let mut terms = EvalTerms {
+        phase,
+        ..Default::default()
+    };
+
+    let mut mg = 0;
+    let mut eg = 0;
+    for piece in board.iter().flatten() {
+        let s = sign(piece.color);
+        terms.material += s * piece_value(piece.piece_type);
+        let (piece_mg, piece_eg) = piece_square(piece.piece_type, piece.color, piece.position);
+        mg += s * piece_mg;
+        eg += s * piece_eg;
+    }
+    terms.piece_squares = (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    for moves in generate_moves(board).iter() {
+        if let Some(mov) = moves.first() {
+            terms.mobility +=
+                sign(mov.piece.color) * mobility_weight(mov.piece.piece_type) * moves.len() as i32;
+        }
+    }
+
+    terms.king_safety =
+        (king_safety(board, Color::White) - king_safety(board, Color::Black)) * phase / MAX_PHASE;
+
+    terms.pawn_structure =
+        pawn_structure(board, Color::White) - pawn_structure(board, Color::Black);
+
+    terms
+}
+
+// Pawns in front of the king and attacked squares next to it, only matters with pieces left
+fn king_safety(board: &Board, color: Color) -> i32 {
+    let king = match find_king(board, color) {
+        Some(king) => king,
+        None => return 0,
+    };
+
+    let mut score = 0;
+    let forward = color as i32;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let x = king.x as i32 + dx;
+            let y = king.y as i32 + dy;
+            if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                continue;
+            }
+            if is_square_attacked(board, Position::from_index((y * 8 + x) as usize), !color) {
+                score -= 8;
+            }
+        }
+
+        for (distance, bonus) in [(1, 10), (2, 5)] {
+            let x = king.x as i32 + dx;
+            let y = king.y as i32 + forward * distance;
+            if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                continue;
+            }
+            if board[(y * 8 + x) as usize]
+                .as_ref()
+                .is_some_and(|p| p.piece_type == PieceType::Pawn && p.color == color)
+            {
+                score += bonus;
+            }
+        }
+    }
+
+    score
+}
+
+fn pawn_structure(board: &Board, color: Color) -> i32 {
+    let pawns = PawnStructure::new(board, color);
+    let mut score = DOUBLED_PAWN * pawns.doubled.len() as i32
+        + ISOLATED_PAWN * pawns.isolated.len() as i32
+        + BACKWARD_PAWN * pawns.backward.len() as i32;
+    for pawn in pawns.passed.iter() {
+        let advanced = if color == Color::White {
+            pawn.y
+        } else {
+            7 - pawn.y
+        };
+        score += PASSED_PAWN[advanced];
+    }
+    score
+}
@@ -0,0 +1,329 @@
+use crate::{Chess, Move, ValidationResult};
+
+pub type NodeId = usize;
+
+/// A position in the tree together with the move that led to it
+#[derive(Clone, Debug)]
+pub struct GameNode {
+    position: Chess,
+    mov: Option<Move>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    pre_comment: String,
+    comment: String,
+    nags: Vec<u8>,
+}
+
+impl GameNode {
+    pub fn position(&self) -> &Chess {
+        &self.position
+    }
+
+    /// The move leading to this node, `None` for the root
+    pub fn mov(&self) -> Option<&Move> {
+        self.mov.as_ref()
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// The moves played from this position, the first one is the main line
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Comment before the move, like one at the start of a variation
+    pub fn pre_comment(&self) -> &str {
+        &self.pre_comment
+    }
+
+    /// Comment after the move, or before the first move for the root
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// Numeric annotation glyphs, e.g. 1 for `!` and 2 for `?`
+    pub fn nags(&self) -> &[u8] {
+        &self.nags
+    }
+}
+
+/// A game with variations, for analysis. Every node holds its position,
+/// so moving around in the tree never needs to replay moves.
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    // Deleted nodes are left as `None` so the ids of other nodes stay the same
+    nodes: Vec<Option<GameNode>>,
+    current: NodeId,
+    /// PGN tags like `Event` and `White`, in the order they are written
+    pub tags: Vec<(String, String)>,
+}
+
+impl Default for GameTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameTree {
+    pub fn new() -> Self {
+        GameTree::from_position(Chess::new())
+    }
+
+    pub fn from_position(position: Chess) -> Self {
+        GameTree {
+            nodes: vec![Some(GameNode {
+                position,
+                mov: None,
+                parent: None,
+                children: Vec::new(),
+                pre_comment: String::new(),
+                comment: String::new(),
+                nags: Vec::new(),
+            })],
+            current: 0,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&GameNode> {
+        self.nodes.get(id).and_then(|n| n.as_ref())
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut GameNode> {
+        self.nodes.get_mut(id).and_then(|n| n.as_mut())
+    }
+
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    pub fn position(&self) -> &Chess {
+        &self.node(self.current).unwrap().position
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Adds the move after the node, as the main line if it's the first move there
+    /// and otherwise as a new variation. Gives back the existing node if the move
+    /// was already added.
+    pub fn add_move(&mut self, parent: NodeId, mov: &Move) -> Result<NodeId, ValidationResult> {
+        let parent_node = self.node(parent).ok_or(ValidationResult::InvalidPosition)?;
+
+        for &child in parent_node.children.iter() {
+            let child_move = self.node(child).unwrap().mov.as_ref().unwrap();
+            if child_move.from == mov.from
+                && child_move.to == mov.to
+                && child_move.promotion == mov.promotion
+            {
+                return Ok(child);
+            }
+        }
+
+        let mut position = parent_node.position.clone();
+        let res = position.play(mov);
+        if !matches!(res, ValidationResult::Valid(_)) {
+            return Err(res);
+        }
+        let played = position.last_move().unwrap().clone();
+
+        let id = self.nodes.len();
+        self.nodes.push(Some(GameNode {
+            position,
+            mov: Some(played),
+            parent: Some(parent),
+            children: Vec::new(),
+            pre_comment: String::new(),
+            comment: String::new(),
+            nags: Vec::new(),
+        }));
+        self.node_mut(parent).unwrap().children.push(id);
+
+        Ok(id)
+    }
+
+    /// Adds the move after the current node and goes to it
+    pub fn play(&mut self, mov: &Move) -> Result<NodeId, ValidationResult> {
+        let id = self.add_move(self.current, mov)?;
+        self.current = id;
+        Ok(id)
+    }
+
+    pub fn go_to(&mut self, id: NodeId) -> bool {
+        if self.node(id).is_none() {
+            return false;
+        }
+        self.current = id;
+        true
+    }
+
+    pub fn go_back(&mut self) -> bool {
+        match self.node(self.current).unwrap().parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Follows the main line one move
+    pub fn go_forward(&mut self) -> bool {
+        match self.node(self.current).unwrap().children.first() {
+            Some(&child) => {
+                self.current = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn go_to_start(&mut self) {
+        self.current = self.root();
+    }
+
+    /// Follows the main line from the current node to its end
+    pub fn go_to_end(&mut self) {
+        while self.go_forward() {}
+    }
+
+    /// The nodes of the main line from the first move to the last
+    pub fn main_line(&self) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut id = self.root();
+        while let Some(&child) = self.node(id).unwrap().children.first() {
+            line.push(child);
+            id = child;
+        }
+        line
+    }
+
+    /// The nodes from the first move to the given node
+    pub fn line_to(&self, id: NodeId) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut node = self.node(id);
+        let mut node_id = id;
+        while let Some(n) = node {
+            if n.parent.is_none() {
+                break;
+            }
+            line.push(node_id);
+            node_id = n.parent.unwrap();
+            node = self.node(node_id);
+        }
+        line.reverse();
+        line
+    }
+
+    /// Moves the variation one step up among its siblings, to the main line if it was second
+    pub fn promote_variation(&mut self, id: NodeId) -> bool {
+        let parent = match self.node(id).and_then(|n| n.parent) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let siblings = &mut self.node_mut(parent).unwrap().children;
+        let index = siblings.iter().position(|&c| c == id).unwrap();
+        if index == 0 {
+            return false;
+        }
+        siblings.swap(index, index - 1);
+        true
+    }
+
+    /// Makes the line leading to the node the main line of the game
+    pub fn make_main_line(&mut self, id: NodeId) -> bool {
+        if self.node(id).is_none() {
+            return false;
+        }
+        let mut node_id = id;
+        while let Some(parent) = self.node(node_id).unwrap().parent {
+            let siblings = &mut self.node_mut(parent).unwrap().children;
+            let index = siblings.iter().position(|&c| c == node_id).unwrap();
+            let node = siblings.remove(index);
+            siblings.insert(0, node);
+            node_id = parent;
+        }
+        true
+    }
+
+    /// Removes the node and everything after it. The root can't be deleted.
+    pub fn delete_variation(&mut self, id: NodeId) -> bool {
+        let parent = match self.node(id).and_then(|n| n.parent) {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        if self.line_to(self.current).contains(&id) {
+            self.current = parent;
+        }
+
+        self.node_mut(parent).unwrap().children.retain(|&c| c != id);
+
+        let mut to_delete = vec![id];
+        while let Some(node_id) = to_delete.pop() {
+            if let Some(node) = self.nodes[node_id].take() {
+                to_delete.extend(node.children);
+            }
+        }
+        true
+    }
+
+    pub fn set_comment(&mut self, id: NodeId, comment: &str) -> bool {
+        match self.node_mut(id) {
+            Some(node) => {
+                node.comment = comment.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_pre_comment(&mut self, id: NodeId, comment: &str) -> bool {
+        match self.node_mut(id) {
+            Some(node) => {
+                node.pre_comment = comment.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn add_nag(&mut self, id: NodeId, nag: u8) -> bool {
+        match self.node_mut(id) {
+            Some(node) => {
+                if !node.nags.contains(&nag) {
+                    node.nags.push(nag);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_nag(&mut self, id: NodeId, nag: u8) -> bool {
+        match self.node_mut(id) {
+            Some(node) => {
+                node.nags.retain(|&n| n != nag);
+                true
+            }
+            None => false,
+        }
+    }
+}
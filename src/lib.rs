@@ -1,14 +1,62 @@
+pub mod annotate;
+pub mod bot;
+pub mod builder;
+pub mod eco;
+pub mod encoding;
+pub mod engine;
+pub mod eval;
+pub mod game_tree;
+pub mod mate;
 pub mod moves;
-use moves::{generate_moves, ValidBoardMoves};
+pub mod pawns;
+pub mod pgn;
+pub mod polyglot;
+pub mod puzzle;
+pub mod render;
+pub mod san;
+pub mod search;
+pub mod see;
+pub mod snapshot;
+pub mod svg;
+pub mod syzygy;
+pub mod tactics;
+pub mod threats;
+pub mod uci;
+pub mod validation;
+pub mod xboard;
+pub use bot::{Bot, SkillLevel};
+pub use builder::BoardBuilder;
+pub use game_tree::GameTree;
+use moves::{generate_moves, is_square_attacked, king_in_check, ValidBoardMoves};
+pub use render::{BoardRenderer, PieceStyle};
+pub use snapshot::ChessSnapshot;
+pub use validation::PositionIssue;
+
+const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
 
 const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Black = -1,
     White = 1,
 }
 
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Color::White => write!(f, "white"),
+            Color::Black => write!(f, "black"),
+        }
+    }
+}
+
 impl std::ops::Not for Color {
     type Output = Self;
 
@@ -21,6 +69,7 @@ impl std::ops::Not for Color {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DrawType {
     Stalemate,
     FiftyMoveRule,
@@ -28,6 +77,7 @@ pub enum DrawType {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Status {
     Chilling,
     Check(Color),
@@ -37,6 +87,7 @@ pub enum Status {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValidationResult {
     Valid(Status),
     InvalidPosition,
@@ -45,6 +96,7 @@ pub enum ValidationResult {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     King,
     Queen,
@@ -54,13 +106,165 @@ pub enum PieceType {
     Pawn,
 }
 
+impl PieceType {
+    /// The uppercase letter used for the piece in FEN
+    pub fn to_char(&self) -> char {
+        match self {
+            PieceType::King => 'K',
+            PieceType::Queen => 'Q',
+            PieceType::Rook => 'R',
+            PieceType::Bishop => 'B',
+            PieceType::Knight => 'N',
+            PieceType::Pawn => 'P',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            'k' => Some(PieceType::King),
+            'q' => Some(PieceType::Queen),
+            'r' => Some(PieceType::Rook),
+            'b' => Some(PieceType::Bishop),
+            'n' => Some(PieceType::Knight),
+            'p' => Some(PieceType::Pawn),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PieceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            PieceType::King => "king",
+            PieceType::Queen => "queen",
+            PieceType::Rook => "rook",
+            PieceType::Bishop => "bishop",
+            PieceType::Knight => "knight",
+            PieceType::Pawn => "pawn",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CastlingType {
     KingSide(Color),
     QueenSide(Color),
 }
 
+impl CastlingType {
+    pub fn color(&self) -> Color {
+        match self {
+            CastlingType::KingSide(c) | CastlingType::QueenSide(c) => *c,
+        }
+    }
+
+    /// Home squares of the king and the rook taking part in the castling
+    pub fn king_and_rook_squares(&self) -> (Position, Position) {
+        let row = if self.color() == Color::White { 0 } else { 7 };
+        match self {
+            CastlingType::KingSide(_) => (Position { x: 4, y: row }, Position { x: 7, y: row }),
+            CastlingType::QueenSide(_) => (Position { x: 4, y: row }, Position { x: 0, y: row }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub const ALL: [CastlingType; 4] = [
+        CastlingType::KingSide(Color::White),
+        CastlingType::QueenSide(Color::White),
+        CastlingType::KingSide(Color::Black),
+        CastlingType::QueenSide(Color::Black),
+    ];
+
+    pub fn all() -> Self {
+        CastlingRights {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    }
+
+    pub fn has(&self, castling_type: CastlingType) -> bool {
+        match castling_type {
+            CastlingType::KingSide(Color::White) => self.white_king_side,
+            CastlingType::QueenSide(Color::White) => self.white_queen_side,
+            CastlingType::KingSide(Color::Black) => self.black_king_side,
+            CastlingType::QueenSide(Color::Black) => self.black_queen_side,
+        }
+    }
+
+    pub fn set(&mut self, castling_type: CastlingType, value: bool) {
+        match castling_type {
+            CastlingType::KingSide(Color::White) => self.white_king_side = value,
+            CastlingType::QueenSide(Color::White) => self.white_queen_side = value,
+            CastlingType::KingSide(Color::Black) => self.black_king_side = value,
+            CastlingType::QueenSide(Color::Black) => self.black_queen_side = value,
+        }
+    }
+
+    pub fn from_fen(s: &str) -> Option<Self> {
+        let mut rights = CastlingRights::default();
+        if s == "-" {
+            return Some(rights);
+        }
+        for c in s.chars() {
+            match c {
+                'K' => rights.white_king_side = true,
+                'Q' => rights.white_queen_side = true,
+                'k' => rights.black_king_side = true,
+                'q' => rights.black_queen_side = true,
+                _ => return None,
+            }
+        }
+        Some(rights)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut s = String::new();
+        for (right, c) in CastlingRights::ALL.iter().zip(['K', 'Q', 'k', 'q']) {
+            if self.has(*right) {
+                s.push(c);
+            }
+        }
+        if s.is_empty() {
+            s.push('-');
+        }
+        s
+    }
+
+    // Rights that are still possible given where the kings and rooks stand
+    fn from_board(board: &Board) -> Self {
+        let mut rights = CastlingRights::default();
+        for right in CastlingRights::ALL {
+            let (king, rook) = right.king_and_rook_squares();
+            let has_piece = |pos: Position, piece_type: PieceType| {
+                board[pos.to_index()]
+                    .as_ref()
+                    .is_some_and(|p| p.piece_type == piece_type && p.color == right.color())
+            };
+            rights.set(
+                right,
+                has_piece(king, PieceType::King) && has_piece(rook, PieceType::Rook),
+            );
+        }
+        rights
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MoveType {
     Normal,
     Castling(CastlingType),
@@ -69,14 +273,28 @@ pub enum MoveType {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Move {
     pub piece: Piece,
     pub from: Position,
     pub to: Position,
     pub take_piece: bool,
+    /// What a pawn reaching the last rank becomes, only used when playing moves with `Chess::play`
+    pub promotion: Option<PieceType>,
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", promotion.to_char().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -88,6 +306,7 @@ impl Position {
         let y = (self.y as u8 + b'1') as char;
         format!("{}{}", x, y)
     }
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         let s = s.to_lowercase();
         let x = s.chars().nth(0).unwrap() as usize - 'a' as usize;
@@ -105,7 +324,14 @@ impl Position {
     }
 }
 
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: Color,
@@ -113,54 +339,82 @@ pub struct Piece {
     prev_positions: Vec<Position>,
 }
 
+impl Piece {
+    pub fn new(piece_type: PieceType, color: Color, position: Position) -> Self {
+        Piece {
+            piece_type,
+            color,
+            position,
+            prev_positions: Vec::new(),
+        }
+    }
+
+    /// The FEN letter of the piece, uppercase for white and lowercase for black
+    pub fn to_char(&self) -> char {
+        match self.color {
+            Color::White => self.piece_type.to_char(),
+            Color::Black => self.piece_type.to_char().to_ascii_lowercase(),
+        }
+    }
+}
+
+impl std::fmt::Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
 pub type Board = [Option<Piece>; 64];
 
 fn hash_board(board: &Board) -> String {
     let mut hash = String::new();
-    for i in 0..64 {
-        if let Some(piece) = &board[i] {
-            let piece_hash = match piece.piece_type {
-                PieceType::King => match piece.color {
-                    Color::White => "1 ",
-                    Color::Black => "2 ",
-                },
-                PieceType::Queen => match piece.color {
-                    Color::White => "3 ",
-                    Color::Black => "4 ",
-                },
-                PieceType::Rook => match piece.color {
-                    Color::White => "5 ",
-                    Color::Black => "6 ",
-                },
-                PieceType::Bishop => match piece.color {
-                    Color::White => "7 ",
-                    Color::Black => "8 ",
-                },
-                PieceType::Knight => match piece.color {
-                    Color::White => "9 ",
-                    Color::Black => "10 ",
-                },
-                PieceType::Pawn => match piece.color {
-                    Color::White => "11 ",
-                    Color::Black => "12 ",
-                },
-            };
-            hash.push_str(piece_hash);
+    for piece in board.iter().flatten() {
+        let piece_hash = match piece.piece_type {
+            PieceType::King => match piece.color {
+                Color::White => "1 ",
+                Color::Black => "2 ",
+            },
+            PieceType::Queen => match piece.color {
+                Color::White => "3 ",
+                Color::Black => "4 ",
+            },
+            PieceType::Rook => match piece.color {
+                Color::White => "5 ",
+                Color::Black => "6 ",
+            },
+            PieceType::Bishop => match piece.color {
+                Color::White => "7 ",
+                Color::Black => "8 ",
+            },
+            PieceType::Knight => match piece.color {
+                Color::White => "9 ",
+                Color::Black => "10 ",
+            },
+            PieceType::Pawn => match piece.color {
+                Color::White => "11 ",
+                Color::Black => "12 ",
+            },
+        };
+        hash.push_str(piece_hash);
 
-            hash.push_str(&format!("{} ", piece.position.to_str()));
-        }
+        hash.push_str(&format!("{} ", piece.position.to_str()));
     }
     hash
 }
 
+#[derive(Clone, Debug)]
 pub struct Chess {
-    pub board: Board,
-    pub turn: Color,
-    pub status: Status,
-    pub winner: Option<Color>,
-    pub awaiting_promotion_piece: Option<Piece>,
+    board: Board,
+    turn: Color,
+    status: Status,
+    winner: Option<Color>,
+    awaiting_promotion_piece: Option<Piece>,
     valid_moves: ValidBoardMoves,
+    castling_rights: CastlingRights,
+    en_passant: Option<Position>,
     counter_50_move_rule: u8,
+    fullmove_number: u16,
+    last_move: Option<Move>,
     prev_boards: std::collections::HashMap<String, u8>,
 }
 
@@ -172,7 +426,7 @@ impl Default for Chess {
 
 impl Chess {
     pub fn new() -> Self {
-        Chess::from_fen(STARTING_FEN).unwrap()
+        Chess::from_fen(STARTING_FEN, false).unwrap()
     }
 
     pub fn parse_fen_board(board_str: &str) -> Option<Board> {
@@ -184,7 +438,7 @@ impl Chess {
         for c in board_str.chars() {
             match c {
                 '/' => {
-                    if row == 0 {
+                    if row == 0 || col != 8 {
                         return None;
                     }
 
@@ -193,12 +447,8 @@ impl Chess {
                 }
                 '1'..='8' => {
                     let n = c.to_digit(10).unwrap() as usize;
-                    for _ in 0..n {
-                        let index = row * 8 + col;
-                        if index >= 64 {
-                            return None;
-                        }
-                        board[index] = None;
+                    if col + n > 8 {
+                        return None;
                     }
                     col += n;
                 }
@@ -208,22 +458,13 @@ impl Chess {
                     } else {
                         Color::Black
                     };
-                    let piece_type = match c.to_ascii_lowercase() {
-                        'k' => PieceType::King,
-                        'q' => PieceType::Queen,
-                        'r' => PieceType::Rook,
-                        'b' => PieceType::Bishop,
-                        'n' => PieceType::Knight,
-                        'p' => PieceType::Pawn,
-                        _ => return None,
-                    };
+                    let piece_type = PieceType::from_char(c)?;
 
-                    let index = row * 8 + col;
-                    if index >= 64 {
+                    if col >= 8 {
                         return None;
                     }
 
-                    board[index] = Some(Piece {
+                    board[row * 8 + col] = Some(Piece {
                         piece_type,
                         color,
                         position: Position { x: col, y: row },
@@ -234,10 +475,16 @@ impl Chess {
             }
         }
 
+        if row != 0 || col != 8 {
+            return None;
+        }
+
         Some(board)
     }
 
-    pub fn from_fen(fen: &str) -> Result<Self, &str> {
+    /// Imports a position from FEN. With `validate` set, positions that can't occur
+    /// in a real game are refused, see `validate_position` for what is checked.
+    pub fn from_fen(fen: &str, validate: bool) -> Result<Self, &str> {
         let mut parts = fen.split_whitespace();
 
         let board_str = parts.next();
@@ -249,6 +496,7 @@ impl Chess {
         if board.is_none() {
             return Err("Invalid board");
         }
+        let board = board.unwrap();
 
         let turn_str = parts.next();
         if turn_str.is_none() {
@@ -260,79 +508,199 @@ impl Chess {
             _ => return Err("Invalid turn"),
         };
 
-        let mut chess = Self {
-            board: board.unwrap(),
-            turn,
-            winner: None,
-            status: Status::Chilling,
-            valid_moves: std::array::from_fn(|_| Vec::new()),
-            awaiting_promotion_piece: None,
-            counter_50_move_rule: 0,
-            prev_boards: std::collections::HashMap::new(),
+        // The remaining fields are optional, a missing castling field means
+        // that every castling still possible on the board is allowed
+        let castling_rights = match parts.next() {
+            Some(s) => match CastlingRights::from_fen(s) {
+                Some(rights) => rights,
+                None => return Err("Invalid castling rights"),
+            },
+            None => CastlingRights::from_board(&board),
         };
-        chess.update(false);
+
+        let en_passant = match parts.next() {
+            None | Some("-") => None,
+            Some(s) => match san::parse_square(s) {
+                Some(square) => Some(square),
+                None => return Err("Invalid en passant square"),
+            },
+        };
+
+        let counter_50_move_rule = match parts.next().map(|s| s.parse::<u8>()) {
+            None => 0,
+            Some(Ok(n)) => n,
+            Some(Err(_)) => return Err("Invalid halfmove clock"),
+        };
+
+        let fullmove_number = match parts.next().map(|s| s.parse::<u16>()) {
+            None => 1,
+            Some(Ok(n)) if n > 0 => n,
+            Some(_) => return Err("Invalid fullmove number"),
+        };
+
+        let chess = Chess::from_parts(
+            board,
+            turn,
+            castling_rights,
+            en_passant,
+            counter_50_move_rule,
+            fullmove_number,
+        );
+        if validate && !chess.validate_position().is_empty() {
+            return Err("Illegal position");
+        }
 
         Ok(chess)
     }
 
-    fn get_move_type(&self, from: Position, to: Position) -> MoveType {
-        let from_index = from.y * 8 + from.x;
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
 
-        let piece = self.board[from_index].as_ref();
+    pub fn piece_at(&self, square: Position) -> Option<&Piece> {
+        self.board[square.to_index()].as_ref()
+    }
 
-        if piece.is_none() {
-            return MoveType::Normal;
-        }
+    pub fn side_to_move(&self) -> Color {
+        self.turn
+    }
 
-        if piece.is_none() {
-            return MoveType::Normal;
-        }
+    pub fn status(&self) -> Status {
+        self.status
+    }
 
-        let piece = piece.unwrap();
+    /// The winner of the game, `None` while the game is running or if it was drawn
+    pub fn winner(&self) -> Option<Color> {
+        self.winner
+    }
 
-        let valid_piece_moves = self.valid_moves[from_index].clone();
+    /// The pawn waiting to be promoted with `promote_piece`
+    pub fn pending_promotion(&self) -> Option<&Piece> {
+        self.awaiting_promotion_piece.as_ref()
+    }
 
-        let mov = valid_piece_moves.iter().find(|m| m.to == to);
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling_rights
+    }
 
-        if mov.is_none() {
-            return MoveType::Normal;
-        }
+    /// The square a pawn can be captured on en passant, set right after a two step pawn move
+    pub fn en_passant(&self) -> Option<Position> {
+        self.en_passant
+    }
+
+    /// Number of half moves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u8 {
+        self.counter_50_move_rule
+    }
+
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
+    pub fn last_move(&self) -> Option<&Move> {
+        self.last_move.as_ref()
+    }
+
+    fn from_parts(
+        board: Board,
+        turn: Color,
+        castling_rights: CastlingRights,
+        en_passant: Option<Position>,
+        counter_50_move_rule: u8,
+        fullmove_number: u16,
+    ) -> Self {
+        let mut chess = Self {
+            board,
+            turn,
+            winner: None,
+            status: Status::Chilling,
+            valid_moves: std::array::from_fn(|_| Vec::new()),
+            awaiting_promotion_piece: None,
+            castling_rights,
+            en_passant,
+            counter_50_move_rule,
+            fullmove_number,
+            last_move: None,
+            prev_boards: std::collections::HashMap::new(),
+        };
+        chess.update(false);
 
-        let mov = mov.unwrap();
+        chess
+    }
 
-        if mov.piece.piece_type == PieceType::Pawn
-            && ((to.y == 0 && piece.color == Color::White)
-                || (to.y == 7 && piece.color == Color::Black))
-        {
-            return MoveType::Promotion;
+    pub fn to_fen(&self) -> String {
+        let mut board_str = String::new();
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                match &self.board[y * 8 + x] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            board_str.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        board_str.push(piece.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                board_str.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                board_str.push('/');
+            }
         }
 
-        let delta = (from.x as i8 - to.x as i8).abs();
+        format!(
+            "{} {} {} {} {} {}",
+            board_str,
+            if self.turn == Color::White { "w" } else { "b" },
+            self.castling_rights.to_fen(),
+            self.en_passant.map_or("-".to_string(), |p| p.to_str()),
+            self.counter_50_move_rule,
+            self.fullmove_number
+        )
+    }
+
+    fn get_move_type(&self, from: Position, to: Position) -> MoveType {
+        let piece = match self.board[from.to_index()].as_ref() {
+            Some(p) => p,
+            None => return MoveType::Normal,
+        };
 
-        if piece.piece_type == PieceType::King && (delta == 3 || delta == 4) {
-            if to.x == 0 {
-                return MoveType::Castling(CastlingType::QueenSide(piece.color));
-            } else if to.x == 7 {
-                return MoveType::Castling(CastlingType::KingSide(piece.color));
+        if piece.piece_type == PieceType::King {
+            // Castling is done by moving the king onto the rook
+            for castling_type in CastlingRights::ALL {
+                let (king, rook) = castling_type.king_and_rook_squares();
+                if castling_type.color() == piece.color && from == king && to == rook {
+                    return MoveType::Castling(castling_type);
+                }
             }
         }
 
-        if mov.piece.piece_type == PieceType::Pawn
-            && (from.x as i8 - to.x as i8).abs() == 1
-            && self.board[to.y * 8 + to.x].is_none()
-        {
-            return MoveType::EnPassant;
+        if piece.piece_type == PieceType::Pawn {
+            if (to.y == 7 && piece.color == Color::White)
+                || (to.y == 0 && piece.color == Color::Black)
+            {
+                return MoveType::Promotion;
+            }
+
+            if from.x != to.x && self.board[to.to_index()].is_none() && self.en_passant == Some(to)
+            {
+                return MoveType::EnPassant;
+            }
         }
 
         MoveType::Normal
     }
 
-    fn move_piece_on_board(&self, board: &Board, from: Position, to: Position) -> (Board, bool) {
-        let from_index = from.y * 8 + from.x;
-        let to_index = to.y * 8 + to.x;
+    fn move_piece_on_board(&self, from: Position, to: Position) -> (Board, bool) {
+        let from_index = from.to_index();
+        let to_index = to.to_index();
 
-        let piece = board[from_index].as_ref().unwrap().clone();
-        let mut board = board.clone();
+        let piece = self.board[from_index].as_ref().unwrap().clone();
+        let mut board = self.board.clone();
 
         let mut prev_positions = piece.prev_positions.clone();
         prev_positions.push(piece.position);
@@ -344,6 +712,7 @@ impl Chess {
         match move_type {
             MoveType::Castling(t) => {
                 board = self.perform_castling(board, t);
+                capture = false;
             }
             MoveType::EnPassant => {
                 board[to_index] = Some(Piece {
@@ -370,128 +739,169 @@ impl Chess {
         (board, capture)
     }
 
-    pub fn generate_valid_moves(&self) -> [Vec<Move>; 64] {
-        const ARRAY_REPEAT_VALUE: Vec<Move> = Vec::new();
-        let mut moves = [ARRAY_REPEAT_VALUE; 64];
+    fn leaves_king_safe(&self, mov: &Move) -> bool {
+        let board = self.move_piece_on_board(mov.from, mov.to).0;
+        !king_in_check(&board, mov.piece.color)
+    }
+
+    fn legal_moves(&self, color: Color) -> ValidBoardMoves {
+        let mut moves: ValidBoardMoves = std::array::from_fn(|_| Vec::new());
 
         let pseudo_possible_moves = generate_moves(&self.board);
 
-        for (i, tile) in pseudo_possible_moves.iter().enumerate() {
+        for (i, tile) in pseudo_possible_moves.into_iter().enumerate() {
             for mov in tile {
-                let validate_board = self
-                    .move_piece_on_board(&self.board, mov.piece.position, mov.to)
-                    .0;
-
-                let new_valid_moves = generate_moves(&validate_board);
+                if mov.piece.color == color && self.leaves_king_safe(&mov) {
+                    moves[i].push(mov);
+                }
+            }
+        }
 
-                if mov.piece.color != self.turn {
-                    continue;
+        // Castling and en passant depend on the game history, so they are only
+        // available for the side to move
+        if color == self.turn {
+            let special_moves = self
+                .castling_moves()
+                .into_iter()
+                .chain(self.en_passant_moves());
+            for mov in special_moves {
+                if self.leaves_king_safe(&mov) {
+                    moves[mov.from.to_index()].push(mov);
                 }
+            }
+        }
 
+        moves
+    }
 
-                let mov_type = self.get_move_type(mov.piece.position, mov.to);
+    pub fn generate_valid_moves(&self) -> [Vec<Move>; 64] {
+        self.legal_moves(self.turn)
+    }
 
-                if let MoveType::Castling(t) = mov_type {
-                    if !self.check_castling_possible(t) {
-                        continue;
-                    }
-                }
-                if !self.check_check(&validate_board, &new_valid_moves).is_some_and(|c| c.contains(&self.turn)) {
-                    moves[i].push(mov.clone());
+    /// All valid moves in one list, where a promotion is listed once for every
+    /// piece the pawn can become. The order is the same every time for a position.
+    pub fn all_valid_moves(&self) -> Vec<Move> {
+        if self.status == Status::AwaitingPromotion {
+            return Vec::new();
+        }
+
+        let mut moves = Vec::new();
+        for mov in self.valid_moves.iter().flatten() {
+            if self.get_move_type(mov.from, mov.to) == MoveType::Promotion {
+                for piece_type in PROMOTION_PIECES {
+                    moves.push(Move {
+                        promotion: Some(piece_type),
+                        ..mov.clone()
+                    });
                 }
+            } else {
+                moves.push(mov.clone());
             }
         }
-
         moves
     }
 
-    pub fn validate_move(&self, from: Position, to: Position) -> ValidationResult {
+    fn check_move(&self, from: Position, to: Position) -> Result<(), ValidationResult> {
         if self.status != Status::Chilling && !matches!(self.status, Status::Check(_)) {
-            return ValidationResult::InvalidTurn;
+            return Err(ValidationResult::InvalidTurn);
         }
 
-        let from_index = from.y * 8 + from.x;
-        let to_index = to.y * 8 + to.x;
-
-        if from_index >= 64 || to_index >= 64 {
-            return ValidationResult::InvalidPosition;
+        if from.x >= 8 || from.y >= 8 || to.x >= 8 || to.y >= 8 {
+            return Err(ValidationResult::InvalidPosition);
         }
 
-        let piece = &self.board[from_index];
+        let piece = &self.board[from.to_index()];
 
         if piece.is_none() {
-            return ValidationResult::InvalidPosition;
+            return Err(ValidationResult::InvalidPosition);
         }
 
-        let piece = piece.as_ref().unwrap();
+        if piece.as_ref().unwrap().color != self.turn {
+            return Err(ValidationResult::InvalidTurn);
+        }
 
-        if piece.color != self.turn {
-            return ValidationResult::InvalidTurn;
+        if !self.valid_moves[from.to_index()].iter().any(|m| m.to == to) {
+            return Err(ValidationResult::InvalidMove);
         }
 
-        let move_type = self.get_move_type(from, to);
+        Ok(())
+    }
 
-        if let MoveType::Castling(t) = move_type {
-            if !self.check_castling_possible(t) {
-                return ValidationResult::InvalidMove;
-            }
+    pub fn validate_move(&self, from: Position, to: Position) -> ValidationResult {
+        if let Err(res) = self.check_move(from, to) {
+            return res;
         }
 
-        let valid_piece_moves = self.valid_moves[from_index].clone();
+        let mut chess = self.clone();
+        chess.move_piece(from, to)
+    }
 
-        if !valid_piece_moves.iter().any(|m| m.to == to) {
-            return ValidationResult::InvalidMove;
+    pub fn move_piece(&mut self, from: Position, to: Position) -> ValidationResult {
+        if let Err(res) = self.check_move(from, to) {
+            return res;
         }
 
-        let validate_board = self.move_piece_on_board(&self.board, from, to).0;
+        let piece = self.board[from.to_index()].as_ref().unwrap().clone();
 
-        let new_valid_moves = generate_moves(&validate_board);
+        self.last_move = self.valid_moves[from.to_index()]
+            .iter()
+            .find(|m| m.to == to)
+            .cloned();
 
-        ValidationResult::Valid(self.get_board_status(
-            !self.turn,
-        ))
-    }
+        let board_res = self.move_piece_on_board(from, to);
+        self.board = board_res.0;
+        let capture = board_res.1;
 
-    fn cant_move(&self, valid_moves: &ValidBoardMoves, color: Color) -> bool {
-        let mut count = 0;
-        for mov in valid_moves.iter() {
-            for m in mov.iter() {
-                if m.piece.color != color {
-                    continue;
-                }
-                count += 1;
-            }
+        self.update_castling_rights(from, to);
+
+        self.en_passant = None;
+        if piece.piece_type == PieceType::Pawn && from.y.abs_diff(to.y) == 2 {
+            self.en_passant = Some(Position {
+                x: from.x,
+                y: (from.y + to.y) / 2,
+            });
         }
-        count == 0
 
-    }
+        if piece.piece_type == PieceType::Pawn || capture {
+            self.counter_50_move_rule = 0;
+        } else {
+            self.counter_50_move_rule = self.counter_50_move_rule.saturating_add(1);
+        }
 
-    pub fn move_piece(&mut self, from: Position, to: Position) -> ValidationResult {
-        let validation_res = self.validate_move(from, to);
-        match validation_res {
-            ValidationResult::Valid(_) => {
-                if validation_res == ValidationResult::Valid(Status::Check(self.turn)) {
-                    return ValidationResult::InvalidMove;
-                }
+        self.update(true);
 
-                let from_index = from.y * 8 + from.x;
-                let piece = self.board[from_index].as_ref().unwrap().clone();
+        ValidationResult::Valid(self.status)
+    }
 
-                let board_res = self.move_piece_on_board(&self.board, from, to);
-                self.board = board_res.0;
-                let capture = board_res.1;
+    /// Plays a move including its promotion, so no `AwaitingPromotion` step is needed.
+    /// A move to the last rank with a pawn must say what to promote to.
+    pub fn play(&mut self, mov: &Move) -> ValidationResult {
+        if let Err(res) = self.check_move(mov.from, mov.to) {
+            return res;
+        }
 
-                if piece.piece_type == PieceType::Pawn || capture {
-                    self.counter_50_move_rule = 0;
-                } else {
-                    self.counter_50_move_rule += 1;
-                }
+        let is_promotion = self.get_move_type(mov.from, mov.to) == MoveType::Promotion;
+        match mov.promotion {
+            Some(piece_type) if !is_promotion || !PROMOTION_PIECES.contains(&piece_type) => {
+                return ValidationResult::InvalidMove
+            }
+            None if is_promotion => return ValidationResult::InvalidMove,
+            _ => {}
+        }
 
-                self.update(true);
+        let res = self.move_piece(mov.from, mov.to);
+        match mov.promotion {
+            Some(piece_type) => ValidationResult::Valid(self.promote_piece(piece_type).unwrap()),
+            None => res,
+        }
+    }
 
-                ValidationResult::Valid(self.status)
+    fn update_castling_rights(&mut self, from: Position, to: Position) {
+        for castling_type in CastlingRights::ALL {
+            let (king, rook) = castling_type.king_and_rook_squares();
+            if from == king || from == rook || to == rook {
+                self.castling_rights.set(castling_type, false);
             }
-            _ => validation_res,
         }
     }
 
@@ -503,17 +913,19 @@ impl Chess {
             return;
         }
         if switch_turn {
+            if self.turn == Color::Black {
+                self.fullmove_number += 1;
+            }
             self.turn = !self.turn;
         }
 
-        self.valid_moves = generate_moves(&self.board);
-
-        let board_status = self.get_board_status(self.turn);
+        self.valid_moves = self.legal_moves(self.turn);
 
-        self.status = board_status;
+        self.status = self.get_board_status();
 
         if let Status::Checkmate(color) = self.status {
             self.winner = Some(!color);
+            return;
         } else if self.status == Status::Draw(DrawType::Stalemate) {
             self.winner = None;
         }
@@ -526,23 +938,29 @@ impl Chess {
         }
     }
 
-    fn get_board_status(
-        &self,
-        turn: Color,
-    ) -> Status {
-        let is_check = self.is_check();
-
-        let stuck = !self.generate_valid_moves().iter().any(|m| !m.is_empty());
-
-        if is_check.is_some() {
+    fn get_board_status(&self) -> Status {
+        let stuck = !self.valid_moves.iter().any(|m| !m.is_empty());
 
+        if king_in_check(&self.board, self.turn) {
             // If the player is in check and can't move
             if stuck {
-                return Status::Checkmate(turn);
+                return Status::Checkmate(self.turn);
             }
 
-            return Status::Check(turn);
-        } else if stuck {
+            return Status::Check(self.turn);
+        }
+
+        // The side that just moved can only be in check in an imported position,
+        // report it as if it was their turn
+        if king_in_check(&self.board, !self.turn) {
+            if !self.legal_moves(!self.turn).iter().any(|m| !m.is_empty()) {
+                return Status::Checkmate(!self.turn);
+            }
+
+            return Status::Check(!self.turn);
+        }
+
+        if stuck {
             return Status::Draw(DrawType::Stalemate);
         }
 
@@ -550,11 +968,9 @@ impl Chess {
     }
 
     pub fn is_check(&self) -> Option<Color> {
-        let check_res = self.check_check(&self.board, &self.valid_moves);
-        match check_res {
-            Some(check_colors) => Some(check_colors[0]),
-            None => None,
-        }
+        [self.turn, !self.turn]
+            .into_iter()
+            .find(|color| king_in_check(&self.board, *color))
     }
 
     pub fn promote_piece(&mut self, piece_type: PieceType) -> Option<Status> {
@@ -569,7 +985,7 @@ impl Chess {
 
         let piece = self.awaiting_promotion_piece.clone().unwrap();
 
-        let index = piece.position.y * 8 + piece.position.x;
+        let index = piece.position.to_index();
 
         self.board[index] = Some(Piece {
             piece_type,
@@ -579,54 +995,15 @@ impl Chess {
         });
 
         self.awaiting_promotion_piece = None;
+        if let Some(last_move) = &mut self.last_move {
+            last_move.promotion = Some(piece_type);
+        }
 
         self.update(true);
 
         Some(self.status)
     }
 
-    fn check_check(&self, board: &Board, valid_moves: &ValidBoardMoves) -> Option<Vec<Color>> {
-        let colors = [Color::White, Color::Black];
-        let mut check_colors: Vec<Color> = Vec::new();
-        for color in colors.iter() {
-            let king = board.iter().find(|p| {
-                if let Some(p) = p {
-                    return p.piece_type == PieceType::King && p.color == *color;
-                }
-                false
-            });
-
-            if king.is_none() {
-                continue;
-            }
-
-            let king = king.unwrap().as_ref().unwrap();
-
-            let mut is_check = false;
-
-            valid_moves.iter().for_each(|n| {
-                if n.iter().any(|m| {
-                    if m.piece.color != king.color && m.to == king.position {
-                        return true;
-                    }
-
-                    false
-                }) {
-                    is_check = true;
-                }
-            });
-
-            if is_check {
-                check_colors.push(king.color);
-            }
-        }
-
-        if check_colors.is_empty() {
-            return None;
-        }
-        Some(check_colors)
-    }
-
     fn check_for_promotion(&self) -> Option<Piece> {
         if self.awaiting_promotion_piece.is_some() {
             return self.awaiting_promotion_piece.clone();
@@ -644,106 +1021,99 @@ impl Chess {
         None
     }
 
-    fn check_castling_possible(&self, castling_type: CastlingType) -> bool {
-        if self.status != Status::Chilling {
-            return false;
-        }
-
-        let (side, row) = match castling_type {
-            CastlingType::QueenSide(c) => (0, if c == Color::White { 0 } else { 7 }),
-            CastlingType::KingSide(c) => (1, if c == Color::White { 0 } else { 7 }),
-        };
-
-        if (row == 0 && self.turn == Color::Black) || (row == 7 && self.turn == Color::White) {
-            return false;
+    fn castling_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+
+        for castling_type in CastlingRights::ALL {
+            if castling_type.color() == self.turn && self.check_castling_possible(castling_type) {
+                let (king, rook) = castling_type.king_and_rook_squares();
+                moves.push(Move {
+                    piece: self.board[king.to_index()].clone().unwrap(),
+                    from: king,
+                    to: rook,
+                    take_piece: false,
+                    promotion: None,
+                });
+            }
         }
 
-        let king_index = row * 8 + 4;
-        let rook_index = row * 8 + (7 * side);
-
-        let king = &self.board[king_index];
-        let rook = &self.board[rook_index];
+        moves
+    }
 
-        if king.is_none() || rook.is_none() {
+    // Checks everything except that the king ends up safe, that is done like for every other move
+    fn check_castling_possible(&self, castling_type: CastlingType) -> bool {
+        if !self.castling_rights.has(castling_type) {
             return false;
         }
 
-        let king = king.as_ref().unwrap();
-        let rook = rook.as_ref().unwrap();
+        let color = castling_type.color();
+        let (king, rook) = castling_type.king_and_rook_squares();
 
-        if !rook.prev_positions.is_empty() || !rook.prev_positions.is_empty() {
+        let is_piece = |pos: Position, piece_type: PieceType| {
+            self.board[pos.to_index()]
+                .as_ref()
+                .is_some_and(|p| p.piece_type == piece_type && p.color == color)
+        };
+        if !is_piece(king, PieceType::King) || !is_piece(rook, PieceType::Rook) {
             return false;
         }
 
-        let mut x = king.position.x as i8;
-        let y = king.position.y as i8;
-
-        let tiles_to_check = if side == 0 { 3 } else { 2 };
-
-        for _ in 0..tiles_to_check {
-            x += if side == 0 { -1 } else { 1 };
-            let tile = &self.board[(y * 8 + x) as usize];
-            if tile.is_some() {
+        for x in (king.x.min(rook.x) + 1)..king.x.max(rook.x) {
+            if self.board[king.y * 8 + x].is_some() {
                 return false;
             }
         }
 
-        for i in 1..3 {
-            let mut validate_board: [Option<Piece>; 64] = self.board.clone();
-
-            let king_to = Position {
-                x: ((king.position.x as i8) + (if side == 0 { -i } else { i })) as usize,
-                y: king.position.y,
+        // The king may not castle out of or through check
+        let direction: isize = if rook.x > king.x { 1 } else { -1 };
+        for step in 0..2 {
+            let tile = Position {
+                x: (king.x as isize + direction * step) as usize,
+                y: king.y,
             };
-            let rook_to = Position {
-                x: ((rook.position.x as i8) + (if side == 0 { 3 } else { -2 })) as usize,
-                y: rook.position.y,
-            };
-            let king_to_index = king_to.y * 8 + king_to.x;
-            let rook_to_index = rook_to.y * 8 + rook_to.x;
-            let king_from_index = king.position.y * 8 + king.position.x;
-            let rook_from_index = rook.position.y * 8 + rook.position.x;
-
-            let mut king_prev_positions = king.prev_positions.clone();
-            king_prev_positions.push(king.position);
-
-            let mut rook_prev_positions = rook.prev_positions.clone();
-            rook_prev_positions.push(rook.position);
-
-            validate_board[rook_to_index] = Some(Piece {
-                piece_type: rook.piece_type,
-                color: rook.color,
-                position: rook_to,
-                prev_positions: rook_prev_positions,
-            });
-
-            validate_board[rook_from_index] = None;
+            if is_square_attacked(&self.board, tile, !color) {
+                return false;
+            }
+        }
 
-            validate_board[king_to_index] = Some(Piece {
-                piece_type: king.piece_type,
-                color: king.color,
-                position: king_to,
-                prev_positions: king_prev_positions,
-            });
+        true
+    }
 
-            validate_board[king_from_index] = None;
+    fn en_passant_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
 
-            let new_valid_moves = generate_moves(&validate_board);
+        let target = match self.en_passant {
+            Some(target) => target,
+            None => return moves,
+        };
 
-            let new_status = self.get_board_status(self.turn);
+        let y = target.y as isize - self.turn as isize;
+        if !(0..8).contains(&y) {
+            return moves;
+        }
 
-            let valid = match new_status {
-                Status::Check(c) => c != self.turn,
-                Status::Checkmate(c) => c != self.turn,
-                Status::Draw(_) => false,
-                _ => true,
+        for x in [target.x as isize - 1, target.x as isize + 1] {
+            if !(0..8).contains(&x) {
+                continue;
+            }
+            let from = Position {
+                x: x as usize,
+                y: y as usize,
             };
-            if !valid {
-                return false;
+            if let Some(p) = &self.board[from.to_index()] {
+                if p.piece_type == PieceType::Pawn && p.color == self.turn {
+                    moves.push(Move {
+                        piece: p.clone(),
+                        from,
+                        to: target,
+                        take_piece: true,
+                        promotion: None,
+                    });
+                }
             }
         }
 
-        true
+        moves
     }
 
     fn perform_castling(&self, board: Board, castling_type: CastlingType) -> Board {
@@ -779,6 +1149,9 @@ impl Chess {
         let mut rook_prev_positions = rook.prev_positions.clone();
         rook_prev_positions.push(rook.position);
 
+        board[king_from_index] = None;
+        board[rook_from_index] = None;
+
         board[king_to_index] = Some(Piece {
             piece_type: king.piece_type,
             color: king.color,
@@ -786,8 +1159,6 @@ impl Chess {
             prev_positions: king_prev_positions,
         });
 
-        board[king_from_index] = None;
-
         board[rook_to_index] = Some(Piece {
             piece_type: rook.piece_type,
             color: rook.color,
@@ -795,22 +1166,32 @@ impl Chess {
             prev_positions: rook_prev_positions,
         });
 
-        board[rook_from_index] = None;
-
         board
     }
 
-    pub fn threefold_rule(&mut self, modify: bool) -> Status {
-        let hash = hash_board(&self.board);
+    fn position_key(&self) -> String {
+        format!(
+            "{}{:?} {}",
+            hash_board(&self.board),
+            self.turn,
+            self.castling_rights.to_fen()
+        )
+    }
+
+    /// Whether the current position has occurred at least three times in the game
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.prev_boards
+            .get(&self.position_key())
+            .is_some_and(|c| *c >= 3)
+    }
 
+    fn threefold_rule(&mut self, modify: bool) -> Status {
         if modify {
-            let entry = self.prev_boards.entry(hash.clone()).or_insert(0);
+            let entry = self.prev_boards.entry(self.position_key()).or_insert(0);
             *entry += 1;
         }
 
-        let count = self.prev_boards.get(&hash);
-
-        if count.is_some_and(|c| *c >= 3) {
+        if self.is_threefold_repetition() {
             return Status::Draw(DrawType::ThreefoldRepetition);
         }
         self.status
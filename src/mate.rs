@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::search::{position_hash, same_move};
+use crate::{Chess, Move, Status};
+
+/// An attacking move that forces mate in time, with every defence against it
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolutionTree {
+    pub mov: Move,
+    /// Empty when the move mates
+    pub defences: Vec<Defence>,
+}
+
+/// A defence and all the attacking moves that still mate in time after it.
+/// More than one answer is a dual.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Defence {
+    pub mov: Move,
+    pub answers: Vec<SolutionTree>,
+}
+
+/// Every key of a mate in N problem, with the full solution of each
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MateSolution {
+    /// The most moves of the side to move
+    pub moves: u32,
+    /// The first moves that force mate in at most `moves` moves
+    pub keys: Vec<SolutionTree>,
+}
+
+impl MateSolution {
+    /// A sound problem has exactly one key
+    pub fn is_unique(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    /// Keys other than the intended one, which spoil the problem
+    pub fn cooks(&self, intended: &Move) -> Vec<&SolutionTree> {
+        self.keys
+            .iter()
+            .filter(|key| !same_move(&key.mov, intended))
+            .collect()
+    }
+}
+
+struct MateSolver {
+    // Whether the position, with the attacker's move just played, is mate in time
+    forced: HashMap<(u64, u32), bool>,
+}
+
+fn play(chess: &Chess, mov: &Move) -> Chess {
+    let mut child = chess.clone();
+    child.play(mov);
+    child
+}
+
+impl MateSolver {
+    // The attacker has just moved and has `moves` more moves to mate
+    fn is_forced(&mut self, chess: &Chess, moves: u32) -> bool {
+        match chess.status() {
+            Status::Checkmate(_) => return true,
+            Status::Draw(_) => return false,
+            _ if moves == 0 => return false,
+            _ => {}
+        }
+
+        let key = (position_hash(chess), moves);
+        if let Some(forced) = self.forced.get(&key) {
+            return *forced;
+        }
+
+        let forced = chess.all_valid_moves().iter().all(|defence| {
+            let position = play(chess, defence);
+            position
+                .all_valid_moves()
+                .iter()
+                .any(|attack| self.is_forced(&play(&position, attack), moves - 1))
+        });
+        self.forced.insert(key, forced);
+        forced
+    }
+
+    fn tree(&mut self, chess: &Chess, mov: &Move, moves: u32) -> SolutionTree {
+        let position = play(chess, mov);
+        let mut defences = Vec::new();
+        if !matches!(position.status(), Status::Checkmate(_)) {
+            for defence in position.all_valid_moves() {
+                let after = play(&position, &defence);
+                let answers = self.keys(&after, moves);
+                defences.push(Defence {
+                    mov: defence,
+                    answers,
+                });
+            }
+        }
+        SolutionTree {
+            mov: mov.clone(),
+            defences,
+        }
+    }
+
+    // The moves that mate in at most `moves` moves, with their solutions
+    fn keys(&mut self, chess: &Chess, moves: u32) -> Vec<SolutionTree> {
+        if moves == 0 {
+            return Vec::new();
+        }
+        let mut keys = Vec::new();
+        for mov in chess.all_valid_moves() {
+            if self.is_forced(&play(chess, &mov), moves - 1) {
+                keys.push(self.tree(chess, &mov, moves - 1));
+            }
+        }
+        keys
+    }
+}
+
+/// Finds every way for the side to move to force mate in at most `moves` moves,
+/// whatever the other side does. Only meant for problems with few moves, since
+/// every line is searched.
+pub fn solve_mate(chess: &Chess, moves: u32) -> MateSolution {
+    let mut solver = MateSolver {
+        forced: HashMap::new(),
+    };
+    MateSolution {
+        moves,
+        keys: solver.keys(chess, moves),
+    }
+}
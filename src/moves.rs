@@ -8,7 +8,6 @@ pub struct PossibleMove {
     pub x: i8,
     pub y: i8,
     pub pre_moves: Option<Vec<(i8, i8)>>,
-    castling: bool,
 }
 
 fn valid_position(x: i8, y: i8) -> bool {
@@ -41,21 +40,13 @@ pub fn generate_moves(board: &Board) -> ValidBoardMoves {
 }
 
 fn validate_pre_moves(board: &Board, possible_move: &PossibleMove) -> bool {
-    match &possible_move.pre_moves {
-        Some(pre_moves) => {
-            let mut valid = true;
-            for (px, py) in pre_moves.iter() {
-                let tile = &board[(py * 8 + px) as usize];
-                if tile.is_some() {
-                    valid = false;
-                    break;
-                }
-            }
-            if !valid {
+    if let Some(pre_moves) = &possible_move.pre_moves {
+        for (px, py) in pre_moves.iter() {
+            let tile = &board[(py * 8 + px) as usize];
+            if tile.is_some() {
                 return false;
             }
         }
-        None => {}
     }
 
     true
@@ -78,7 +69,7 @@ fn validate_possible_moves(
             let tile = &board[(y * 8 + x) as usize];
             match tile {
                 Some(p) => {
-                    if p.color != piece.color || possible_move.castling {
+                    if p.color != piece.color {
                         valid_moves.push(Move {
                             piece: piece.clone(),
                             from: piece.position,
@@ -86,7 +77,8 @@ fn validate_possible_moves(
                                 x: x as usize,
                                 y: y as usize,
                             },
-                            take_piece: !possible_move.castling,
+                            take_piece: true,
+                            promotion: None,
                         });
                     }
                 }
@@ -99,6 +91,7 @@ fn validate_possible_moves(
                             y: y as usize,
                         },
                         take_piece: false,
+                        promotion: None,
                     });
                 }
             }
@@ -128,7 +121,6 @@ fn generate_directional_possible_moves(
                 x,
                 y,
                 pre_moves: Some(prev_moves.clone()),
-                castling: false,
             });
 
             prev_moves.push((x, y));
@@ -143,7 +135,6 @@ fn relative_to_absolut_move(piece: &Piece, relative_move: (i8, i8)) -> PossibleM
         x: piece.position.x as i8 + relative_move.0,
         y: piece.position.y as i8 + relative_move.1,
         pre_moves: None,
-        castling: false,
     }
 }
 
@@ -166,10 +157,7 @@ fn valid_moves_king(board: &Board, piece: &Piece) -> Vec<Move> {
         (-1, -1),
     ];
 
-    let mut possible_moves = relative_to_absolute_moves(piece, moves);
-
-    let castling_moves = valid_castling_moves(board, piece);
-    possible_moves.extend(castling_moves);
+    let possible_moves = relative_to_absolute_moves(piece, moves);
 
     validate_possible_moves(board, piece, possible_moves)
 }
@@ -227,15 +215,16 @@ fn valid_moves_pawn(board: &Board, piece: &Piece) -> Vec<Move> {
             x: x + (piece.position.x as i8),
             y: y * (piece.color as i8) + (piece.position.y as i8),
             pre_moves: None,
-            castling: false,
         }
     }
 
     if (piece.color == Color::White && piece.position.y == 1)
         || (piece.color == Color::Black && piece.position.y == 6)
     {
-        let mov = convert_move(piece, 0, 2);
-        if valid_position(mov.x, mov.y) {
+        let mut mov = convert_move(piece, 0, 2);
+        let between = convert_move(piece, 0, 1);
+        mov.pre_moves = Some(vec![(between.x, between.y)]);
+        if valid_position(mov.x, mov.y) && validate_pre_moves(board, &mov) {
             let target_tile = &board[(mov.y * 8 + mov.x) as usize];
             if target_tile.is_none() {
                 moves.push(mov);
@@ -267,96 +256,118 @@ fn valid_moves_pawn(board: &Board, piece: &Piece) -> Vec<Move> {
         }
     }
 
-    let left = convert_move(piece, -1, 0);
-    let right = convert_move(piece, 1, 0);
-
-    if valid_position(left.x, left.y) {
-        let left_tile = &board[(left.y * 8 + left.x) as usize];
-
-        if left_tile.as_ref().is_some_and(|p| {
-            p.color != piece.color
-                && p.prev_positions.len() == 1
-                && ((p.prev_positions[0].y as i8) - (p.position.y as i8)).abs() == 2
-        }) {
-            let left_up = convert_move(piece, -1, 1);
-            if valid_position(left_up.x, left_up.y) {
-                let target_tile = &board[(left_up.y * 8 + left_up.x) as usize];
-                if target_tile.is_none() {
-                    moves.push(left_up);
-                }
-            }
-        }
+    validate_possible_moves(board, piece, moves)
+}
+
+fn piece_at(board: &Board, x: i8, y: i8) -> Option<&Piece> {
+    if !valid_position(x, y) {
+        return None;
     }
+    board[(y * 8 + x) as usize].as_ref()
+}
 
-    if valid_position(right.x, right.y) {
-        let right_tile = &board[(right.y * 8 + right.x) as usize];
-
-        if right_tile.as_ref().is_some_and(|p| {
-            p.color != piece.color
-                && p.prev_positions.len() == 1
-                && ((p.prev_positions[0].y as i8) - (p.position.y as i8)).abs() == 2
-        }) {
-            let right_up = convert_move(piece, 1, 1);
-            if valid_position(right_up.x, right_up.y) {
-                let target_tile = &board[(right_up.y * 8 + right_up.x) as usize];
-                if target_tile.is_none() {
-                    moves.push(right_up);
-                }
+// Walks from the square in each direction and returns the first piece hit
+fn first_pieces_in_directions<'a>(
+    board: &'a Board,
+    square: Position,
+    directions: &[(i8, i8)],
+) -> Vec<&'a Piece> {
+    let mut pieces = Vec::new();
+    for dir in directions.iter() {
+        let mut x = square.x as i8 + dir.0;
+        let mut y = square.y as i8 + dir.1;
+        while valid_position(x, y) {
+            if let Some(p) = piece_at(board, x, y) {
+                pieces.push(p);
+                break;
             }
+            x += dir.0;
+            y += dir.1;
         }
     }
-
-    validate_possible_moves(board, piece, moves)
+    pieces
 }
 
-fn valid_castling_moves(board: &Board, piece: &Piece) -> Vec<PossibleMove> {
-    let row = if piece.color == Color::White { 0 } else { 7 };
-    /* let (side, row) = match castling_type {
-        CastlingType::QueenSide(c) => (0, if c == Color::White { 0 } else { 7 }),
-        CastlingType::KingSide(c) => (1, if c == Color::White { 0 } else { 7 }),
-    }; */
-
-    let mut moves = Vec::new();
+/// Returns all pieces of the given color that attack the square.
+/// Unlike `generate_moves` this also counts attacks on empty squares and own pieces.
+pub fn attackers(board: &Board, square: Position, by: Color) -> Vec<Position> {
+    let mut attackers = Vec::new();
+    let x = square.x as i8;
+    let y = square.y as i8;
 
-    for side in 0..2 {
-        let king_index = row * 8 + 4;
-        let rook_index = row * 8 + (7 * side);
+    let knight_moves = [
+        (2, 1),
+        (1, 2),
+        (-1, 2),
+        (-2, 1),
+        (-2, -1),
+        (-1, -2),
+        (1, -2),
+        (2, -1),
+    ];
+    for (dx, dy) in knight_moves.iter() {
+        if let Some(p) = piece_at(board, x + dx, y + dy) {
+            if p.color == by && p.piece_type == PieceType::Knight {
+                attackers.push(p.position);
+            }
+        }
+    }
 
-        if board[king_index].is_none() || board[rook_index].is_none() {
-            continue;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            if let Some(p) = piece_at(board, x + dx, y + dy) {
+                if p.color == by && p.piece_type == PieceType::King {
+                    attackers.push(p.position);
+                }
+            }
         }
+    }
 
-        let king = board[king_index].as_ref().unwrap();
-        let rook = board[rook_index].as_ref().unwrap();
+    // A pawn attacks diagonally forward, so look one step "backwards" from the square
+    for dx in [-1, 1] {
+        if let Some(p) = piece_at(board, x + dx, y - by as i8) {
+            if p.color == by && p.piece_type == PieceType::Pawn {
+                attackers.push(p.position);
+            }
+        }
+    }
 
-        if !rook.prev_positions.is_empty() || !rook.prev_positions.is_empty() {
-            continue;
+    let straight = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    for p in first_pieces_in_directions(board, square, &straight) {
+        if p.color == by && matches!(p.piece_type, PieceType::Rook | PieceType::Queen) {
+            attackers.push(p.position);
         }
+    }
 
-        let mut x = king.position.x as i8;
-        let y = king.position.y as i8;
+    let diagonal = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+    for p in first_pieces_in_directions(board, square, &diagonal) {
+        if p.color == by && matches!(p.piece_type, PieceType::Bishop | PieceType::Queen) {
+            attackers.push(p.position);
+        }
+    }
 
-        let tiles_to_check = if side == 0 { 3 } else { 2 };
+    attackers
+}
 
-        for _ in 0..tiles_to_check {
-            x += if side == 0 { -1 } else { 1 };
-            if board[(y * 8 + x) as usize].is_some() {
-                continue;
-            }
-        }
+pub fn is_square_attacked(board: &Board, square: Position, by: Color) -> bool {
+    !attackers(board, square, by).is_empty()
+}
 
-        let king_to = Position {
-            x: ((king.position.x as i8) + (if side == 0 { -4 } else { 3 })) as usize,
-            y: king.position.y,
-        };
+pub fn find_king(board: &Board, color: Color) -> Option<Position> {
+    board
+        .iter()
+        .flatten()
+        .find(|p| p.piece_type == PieceType::King && p.color == color)
+        .map(|p| p.position)
+}
 
-        moves.push(PossibleMove {
-            x: king_to.x as i8,
-            y: king_to.y as i8,
-            pre_moves: None,
-            castling: true,
-        });
+/// Whether the king of the given color is attacked. A board without that king is never in check.
+pub fn king_in_check(board: &Board, color: Color) -> bool {
+    match find_king(board, color) {
+        Some(king) => is_square_attacked(board, king, !color),
+        None => false,
     }
-
-    moves
 }
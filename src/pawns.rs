@@ -0,0 +1,118 @@
+use crate::{Board, Color, PieceType, Position};
+
+/// The pawn weaknesses and strengths of one color, as the squares of the pawns
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PawnStructure {
+    /// Pawns with no pawns of their own on the files next to them
+    pub isolated: Vec<Position>,
+    /// Pawns behind another pawn of their own on the same file
+    pub doubled: Vec<Position>,
+    /// Pawns that no pawn of their own can defend and that can't advance
+    /// without being taken by a pawn
+    pub backward: Vec<Position>,
+    /// Pawns with no pawns of the other side in front of them on their own or the files next to them
+    pub passed: Vec<Position>,
+    /// Groups of pawns on files next to each other
+    pub islands: Vec<Vec<Position>>,
+    /// Pawns defending each other diagonally, from the base of the chain forward
+    pub chains: Vec<Vec<Position>>,
+}
+
+fn pawn_at(board: &Board, x: i32, y: i32, color: Color) -> bool {
+    (0..8).contains(&x)
+        && (0..8).contains(&y)
+        && board[(y * 8 + x) as usize]
+            .as_ref()
+            .is_some_and(|p| p.piece_type == PieceType::Pawn && p.color == color)
+}
+
+impl PawnStructure {
+    pub fn new(board: &Board, color: Color) -> Self {
+        let forward = color as i32;
+        let pawns: Vec<Position> = board
+            .iter()
+            .flatten()
+            .filter(|p| p.piece_type == PieceType::Pawn && p.color == color)
+            .map(|p| p.position)
+            .collect();
+        let enemy: Vec<Position> = board
+            .iter()
+            .flatten()
+            .filter(|p| p.piece_type == PieceType::Pawn && p.color != color)
+            .map(|p| p.position)
+            .collect();
+        // How far ahead of `from` the square is for this color
+        let ahead = |from: &Position, to: &Position| (to.y as i32 - from.y as i32) * forward;
+
+        let mut files = [0; 8];
+        for pawn in pawns.iter() {
+            files[pawn.x] += 1;
+        }
+        let has_neighbour = |x: usize| (x > 0 && files[x - 1] > 0) || (x < 7 && files[x + 1] > 0);
+
+        let mut structure = PawnStructure::default();
+        for pawn in pawns.iter() {
+            let (x, y) = (pawn.x as i32, pawn.y as i32);
+
+            if !has_neighbour(pawn.x) {
+                structure.isolated.push(*pawn);
+            } else {
+                // Backward when the pawns on the files next to it have all gone past it
+                // and an enemy pawn covers the square in front of it
+                let supported = pawns
+                    .iter()
+                    .any(|p| p.x.abs_diff(pawn.x) == 1 && ahead(pawn, p) <= 0);
+                let stop_attacked = pawn_at(board, x - 1, y + 2 * forward, !color)
+                    || pawn_at(board, x + 1, y + 2 * forward, !color);
+                if !supported && stop_attacked {
+                    structure.backward.push(*pawn);
+                }
+            }
+
+            if pawns.iter().any(|p| p.x == pawn.x && ahead(pawn, p) > 0) {
+                structure.doubled.push(*pawn);
+            }
+
+            if !enemy
+                .iter()
+                .any(|p| p.x.abs_diff(pawn.x) <= 1 && ahead(pawn, p) > 0)
+            {
+                structure.passed.push(*pawn);
+            }
+        }
+
+        let mut island = Vec::new();
+        for x in 0..8 {
+            if files[x] == 0 {
+                continue;
+            }
+            island.extend(pawns.iter().filter(|p| p.x == x));
+            if x == 7 || files[x + 1] == 0 {
+                structure.islands.push(std::mem::take(&mut island));
+            }
+        }
+
+        // Pawns are joined to the ones defending them, starting from the ones furthest back
+        let mut sorted = pawns.clone();
+        sorted.sort_by_key(|p| p.y as i32 * forward);
+        let mut chain_of: Vec<Option<usize>> = vec![None; 64];
+        let mut chains: Vec<Vec<Position>> = Vec::new();
+        for pawn in sorted.iter() {
+            let (x, y) = (pawn.x as i32, pawn.y as i32);
+            let defender = [x - 1, x + 1]
+                .into_iter()
+                .filter(|&dx| pawn_at(board, dx, y - forward, color))
+                .find_map(|dx| chain_of[((y - forward) * 8 + dx) as usize]);
+            let chain = defender.unwrap_or_else(|| {
+                chains.push(Vec::new());
+                chains.len() - 1
+            });
+            chains[chain].push(*pawn);
+            chain_of[pawn.to_index()] = Some(chain);
+        }
+        structure.chains = chains.into_iter().filter(|c| c.len() > 1).collect();
+
+        structure
+    }
+}
@@ -0,0 +1,361 @@
+use crate::game_tree::{GameTree, NodeId};
+use crate::{Chess, Color, STARTING_FEN};
+
+const LINE_WIDTH: usize = 80;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(String),
+    San(String),
+}
+
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn read_tag(chars: &mut std::iter::Peekable<std::str::Chars>) -> Token {
+    let mut name = String::new();
+    let mut value = String::new();
+
+    while let Some(c) = chars.next_if(|c| *c != '"' && *c != ']') {
+        if !c.is_whitespace() {
+            name.push(c);
+        }
+    }
+    if chars.next_if_eq(&'"').is_some() {
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => value.extend(chars.next()),
+                '"' => break,
+                _ => value.push(c),
+            }
+        }
+    }
+    for c in chars.by_ref() {
+        if c == ']' {
+            break;
+        }
+    }
+
+    Token::Tag(name, value)
+}
+
+fn tokenize(pgn: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        // Lines starting with % are escaped and ignored
+        if line_start && c == '%' {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        line_start = c == '\n';
+
+        match c {
+            c if c.is_whitespace() => {}
+            '[' => tokens.push(read_tag(&mut chars)),
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|c| *c != '\n').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                line_start = true;
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '$' => {
+                let mut number = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                    number.push(c);
+                }
+                if let Ok(nag) = number.parse() {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(c) =
+                    chars.next_if(|c| !c.is_whitespace() && !"(){}[];$".contains(*c))
+                {
+                    word.push(c);
+                }
+                tokenize_word(&word, &mut tokens);
+            }
+        }
+    }
+
+    tokens
+}
+
+fn tokenize_word(word: &str, tokens: &mut Vec<Token>) {
+    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        tokens.push(Token::Result(word.to_string()));
+        return;
+    }
+
+    let mut word = word;
+    // Move numbers, which can be written right before the move like `1.e4`
+    if word.starts_with(|c: char| c.is_ascii_digit()) && !word.starts_with("0-0") {
+        word = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    let word = word.trim_start_matches('.');
+    if word.is_empty() {
+        return;
+    }
+
+    let san = word.trim_end_matches(['!', '?']);
+    if !san.is_empty() {
+        tokens.push(Token::San(san.to_string()));
+    }
+    if let Some(nag) = suffix_to_nag(&word[san.len()..]) {
+        tokens.push(Token::Nag(nag));
+    }
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+fn is_tag(token: &Token) -> bool {
+    matches!(token, Token::Tag(..))
+}
+
+fn join_comments(old: &str, comment: String) -> String {
+    if old.is_empty() {
+        comment
+    } else {
+        format!("{} {}", old, comment)
+    }
+}
+
+// Reads one game, stopping after the result or before the tags of the next game
+fn read_game(tokens: &mut Tokens) -> Result<GameTree, &'static str> {
+    let mut tags = Vec::new();
+    while let Some(Token::Tag(name, value)) = tokens.next_if(is_tag) {
+        tags.push((name, value));
+    }
+
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Chess::from_fen(fen, false).map_err(|_| "Invalid FEN")?,
+        None => Chess::new(),
+    };
+    let mut tree = GameTree::from_position(start);
+    tree.tags = tags;
+
+    // The node after the last move read, and where to continue after each open variation
+    let mut current = tree.root();
+    let mut stack: Vec<NodeId> = Vec::new();
+    // Comments between the start of a variation and its first move, which belong to that move
+    let mut pre_comment: Option<String> = None;
+
+    // The tags of the next game are left for it
+    while let Some(token) = tokens.next_if(|t| !is_tag(t)) {
+        match token {
+            Token::Tag(..) => unreachable!("tags are left in the tokens by next_if"),
+            Token::Comment(comment) => match pre_comment.as_mut() {
+                Some(pre_comment) => *pre_comment = join_comments(pre_comment, comment),
+                None => {
+                    let comment = join_comments(tree.node(current).unwrap().comment(), comment);
+                    tree.set_comment(current, &comment);
+                }
+            },
+            Token::Nag(nag) => {
+                tree.add_nag(current, nag);
+            }
+            Token::Open => {
+                // A variation replaces the last move, so it starts from the position before it
+                let parent = tree
+                    .node(current)
+                    .unwrap()
+                    .parent()
+                    .ok_or("Variation before the first move")?;
+                stack.push(current);
+                current = parent;
+                pre_comment = Some(String::new());
+            }
+            Token::Close => {
+                current = stack.pop().ok_or("Unbalanced parentheses")?;
+                // A variation without moves leaves its comment to the move it was for
+                if let Some(comment) = pre_comment.take().filter(|c| !c.is_empty()) {
+                    let comment = join_comments(tree.node(current).unwrap().comment(), comment);
+                    tree.set_comment(current, &comment);
+                }
+            }
+            Token::Result(result) => {
+                if result != "*" && tree.tag("Result").is_none() {
+                    tree.set_tag("Result", &result);
+                }
+                break;
+            }
+            Token::San(san) => {
+                let mov = tree
+                    .node(current)
+                    .unwrap()
+                    .position()
+                    .parse_san(&san)
+                    .ok_or("Illegal move")?;
+                current = tree.add_move(current, &mov).map_err(|_| "Illegal move")?;
+                if let Some(pre_comment) = pre_comment.take() {
+                    tree.set_pre_comment(current, &pre_comment);
+                }
+            }
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err("Unbalanced parentheses");
+    }
+
+    Ok(tree)
+}
+
+/// Reads every game in the PGN text. A game that can't be read gives an error
+/// and the reading continues with the next game.
+pub fn read_games(pgn: &str) -> Vec<Result<GameTree, &'static str>> {
+    let mut tokens = tokenize(pgn).into_iter().peekable();
+    let mut games = Vec::new();
+
+    while tokens.peek().is_some() {
+        let game = read_game(&mut tokens);
+        if game.is_err() {
+            // Skip the rest of the broken game
+            while let Some(token) = tokens.next_if(|t| !is_tag(t)) {
+                if matches!(token, Token::Result(_)) {
+                    break;
+                }
+            }
+        }
+        games.push(game);
+    }
+
+    games
+}
+
+impl GameTree {
+    /// Reads the first game in the PGN text, including comments, NAGs and variations.
+    /// Games starting from a position use the `FEN` tag.
+    pub fn from_pgn(pgn: &str) -> Result<GameTree, &'static str> {
+        read_game(&mut tokenize(pgn).into_iter().peekable())
+    }
+
+    /// Writes the game as PGN with all variations, comments and NAGs
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+
+        let mut tags = self.tags.clone();
+        let fen = self.node(self.root()).unwrap().position().to_fen();
+        if fen != STARTING_FEN && !tags.iter().any(|(name, _)| name == "FEN") {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen));
+        }
+        for (name, value) in tags.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        if !tags.is_empty() {
+            pgn.push('\n');
+        }
+
+        let mut tokens = Vec::new();
+        let root = self.node(self.root()).unwrap();
+        if !root.comment().is_empty() {
+            tokens.push(format!("{{{}}}", root.comment()));
+        }
+        self.write_line(self.root(), true, &mut tokens);
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        // Join the parentheses with the moves inside them
+        let mut i = 0;
+        while i < tokens.len() {
+            if tokens[i] == "(" && i + 1 < tokens.len() {
+                let next = tokens.remove(i + 1);
+                tokens[i].push_str(&next);
+            } else if tokens[i] == ")" && i > 0 {
+                tokens.remove(i);
+                tokens[i - 1].push(')');
+                continue;
+            }
+            i += 1;
+        }
+
+        let mut line_len = 0;
+        for word in tokens.iter().flat_map(|t| t.split(' ')) {
+            if line_len > 0 && line_len + 1 + word.len() > LINE_WIDTH {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            pgn.push_str(word);
+            line_len += word.len();
+        }
+        pgn.push('\n');
+
+        pgn
+    }
+
+    // Writes the main line after the node, with the other moves at each step as variations
+    fn write_line(&self, from: NodeId, mut show_number: bool, tokens: &mut Vec<String>) {
+        let mut id = from;
+        while let Some((&main, variations)) = self.node(id).unwrap().children().split_first() {
+            self.write_move(main, show_number, tokens);
+
+            for &variation in variations {
+                tokens.push("(".to_string());
+                self.write_move(variation, true, tokens);
+                let has_comment = !self.node(variation).unwrap().comment().is_empty();
+                self.write_line(variation, has_comment, tokens);
+                tokens.push(")".to_string());
+            }
+
+            // The move number is repeated after anything breaking up the moves
+            show_number = !variations.is_empty() || !self.node(main).unwrap().comment().is_empty();
+            id = main;
+        }
+    }
+
+    fn write_move(&self, id: NodeId, show_number: bool, tokens: &mut Vec<String>) {
+        let node = self.node(id).unwrap();
+        let before = self.node(node.parent().unwrap()).unwrap().position();
+
+        let number = before.fullmove_number();
+        let san = before.to_san(node.mov().unwrap());
+        if !node.pre_comment().is_empty() {
+            tokens.push(format!("{{{}}}", node.pre_comment()));
+        }
+        if before.side_to_move() == Color::White {
+            tokens.push(format!("{}. {}", number, san));
+        } else if show_number || !node.pre_comment().is_empty() {
+            tokens.push(format!("{}... {}", number, san));
+        } else {
+            tokens.push(san);
+        }
+
+        for nag in node.nags() {
+            tokens.push(format!("${}", nag));
+        }
+        if !node.comment().is_empty() {
+            tokens.push(format!("{{{}}}", node.comment()));
+        }
+    }
+}
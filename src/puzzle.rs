@@ -0,0 +1,256 @@
+use crate::eval::MATE_SCORE;
+use crate::game_tree::{GameTree, NodeId};
+use crate::search::{is_capture, same_move, SearchLimits, SearchResult, Searcher, MATE_THRESHOLD};
+use crate::tactics::{move_motifs, Motif};
+use crate::uci::move_to_uci;
+use crate::{Chess, Color, Move, PieceType, Status};
+
+/// What a puzzle is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Theme {
+    Mate,
+    /// A mate by a rook or queen on the rank behind the king
+    BackRank,
+    Promotion,
+    /// A piece attacking two or more pieces at once
+    Fork,
+    /// A piece tied to its king or a more valuable piece behind it
+    Pin,
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Theme::Mate => "mate",
+            Theme::BackRank => "back-rank",
+            Theme::Promotion => "promotion",
+            Theme::Fork => "fork",
+            Theme::Pin => "pin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A position from a game where the side to move has a single winning move
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Puzzle {
+    /// The node in the game with the puzzle position
+    pub node: NodeId,
+    pub fen: String,
+    /// The winning line, starting and ending with a move of the side to move
+    pub solution: Vec<Move>,
+    pub uci: Vec<String>,
+    pub san: Vec<String>,
+    /// Centipawns for the side to move after the solution, as found by the search
+    pub score: i32,
+    /// The game went on with another move, or ended here
+    pub missed: bool,
+    pub themes: Vec<Theme>,
+}
+
+/// Finds puzzles in games: positions where the last move gave the other side a
+/// winning tactic that only one move takes advantage of
+#[derive(Clone, Debug)]
+pub struct PuzzleFinder {
+    pub limits: SearchLimits,
+    /// The least score, in centipawns for the side to move, that counts as winning
+    pub winning: i32,
+    /// How much the last move must have gained the side to move
+    pub min_swing: i32,
+    searcher: Searcher,
+}
+
+impl Default for PuzzleFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PuzzleFinder {
+    pub fn new() -> Self {
+        PuzzleFinder {
+            limits: SearchLimits::default(),
+            winning: 200,
+            min_swing: 300,
+            searcher: Searcher::new(),
+        }
+    }
+
+    /// Searches every position of the main line and returns the puzzles in the order they were found
+    pub fn find(&mut self, tree: &GameTree) -> Vec<Puzzle> {
+        self.searcher.clear();
+        let mut nodes = vec![tree.root()];
+        nodes.extend(tree.main_line());
+        let results: Vec<SearchResult> = nodes
+            .iter()
+            .map(|&id| {
+                let position = tree.node(id).unwrap().position();
+                self.searcher.search(position, &self.limits)
+            })
+            .collect();
+
+        let mut puzzles = Vec::new();
+        for i in 1..nodes.len() {
+            let result = &results[i];
+            let before = -results[i - 1].score;
+            if result.best_move.is_none()
+                || result.score < self.winning
+                || before >= self.winning
+                || result.score - before < self.min_swing
+            {
+                continue;
+            }
+
+            let chess = tree.node(nodes[i]).unwrap().position();
+            if !self.is_only_winning_move(chess, result.best_move.as_ref().unwrap()) {
+                continue;
+            }
+
+            let played = tree
+                .node(nodes[i])
+                .unwrap()
+                .children()
+                .first()
+                .map(|&id| tree.node(id).unwrap().mov().unwrap());
+            let missed = played.is_none_or(|mov| !same_move(mov, &result.pv[0]));
+            let solution = self.solution(chess, result);
+            puzzles.push(make_puzzle(chess, nodes[i], solution, result.score, missed));
+        }
+
+        puzzles
+    }
+
+    // The line until the mate, or until the last capture or promotion that wins the material
+    fn solution(&mut self, chess: &Chess, result: &SearchResult) -> Vec<Move> {
+        let mut line = result.pv.clone();
+        if result.score >= MATE_THRESHOLD {
+            let mut position = chess.clone();
+            for mov in line.iter() {
+                position.play(mov);
+            }
+            // The line from the search can be cut short by the transposition table
+            let plies = (MATE_SCORE - result.score) as usize;
+            while line.len() < plies {
+                let Some(mov) = self.searcher.search(&position, &self.limits).best_move else {
+                    break;
+                };
+                position.play(&mov);
+                line.push(mov);
+            }
+            return line;
+        }
+
+        let mut position = chess.clone();
+        let mut end = 1;
+        for (i, mov) in line.iter().enumerate() {
+            if i % 2 == 0 && (is_capture(&position, mov) || mov.promotion.is_some()) {
+                end = i + 1;
+            }
+            position.play(mov);
+        }
+        line.truncate(end);
+        line
+    }
+
+    fn is_only_winning_move(&mut self, chess: &Chess, best: &Move) -> bool {
+        let limits = SearchLimits {
+            depth: self.limits.depth.saturating_sub(1).max(1),
+            ..self.limits
+        };
+        chess
+            .all_valid_moves()
+            .iter()
+            .filter(|mov| !same_move(mov, best))
+            .all(|mov| {
+                let mut child = chess.clone();
+                child.play(mov);
+                -self.searcher.search(&child, &limits).score < self.winning
+            })
+    }
+}
+
+fn make_puzzle(
+    chess: &Chess,
+    node: NodeId,
+    solution: Vec<Move>,
+    score: i32,
+    missed: bool,
+) -> Puzzle {
+    let mut positions = vec![chess.clone()];
+    for mov in solution.iter() {
+        let mut next = positions.last().unwrap().clone();
+        next.play(mov);
+        positions.push(next);
+    }
+
+    let uci = solution
+        .iter()
+        .zip(positions.iter())
+        .map(|(mov, position)| move_to_uci(position, mov))
+        .collect();
+    let san = solution
+        .iter()
+        .zip(positions.iter())
+        .map(|(mov, position)| position.to_san(mov))
+        .collect();
+
+    Puzzle {
+        node,
+        fen: chess.to_fen(),
+        themes: themes(&solution, &positions),
+        solution,
+        uci,
+        san,
+        score,
+        missed,
+    }
+}
+
+fn themes(solution: &[Move], positions: &[Chess]) -> Vec<Theme> {
+    let mut themes = Vec::new();
+    let last = positions.last().unwrap();
+    if let Status::Checkmate(_) = last.status() {
+        themes.push(Theme::Mate);
+        if is_back_rank_mate(last, solution.last().unwrap()) {
+            themes.push(Theme::BackRank);
+        }
+    }
+
+    // Only the moves of the solver, with the position before each
+    let moves = solution.iter().zip(positions.iter()).step_by(2);
+    if moves.clone().any(|(mov, _)| mov.promotion.is_some()) {
+        themes.push(Theme::Promotion);
+    }
+    let motifs: Vec<Motif> = moves
+        .flat_map(|(mov, before)| move_motifs(before, mov))
+        .collect();
+    if motifs.iter().any(|m| matches!(m, Motif::Fork { .. })) {
+        themes.push(Theme::Fork);
+    }
+    if motifs.iter().any(|m| matches!(m, Motif::Pin { .. })) {
+        themes.push(Theme::Pin);
+    }
+    themes
+}
+
+fn is_back_rank_mate(chess: &Chess, mov: &Move) -> bool {
+    let color = chess.side_to_move();
+    let back_rank = if color == Color::White { 0 } else { 7 };
+    let king_on_back_rank =
+        chess.board().iter().flatten().any(|p| {
+            p.piece_type == PieceType::King && p.color == color && p.position.y == back_rank
+        });
+    king_on_back_rank
+        && mov.to.y == back_rank
+        && matches!(
+            chess.piece_at(mov.to).map(|p| p.piece_type),
+            Some(PieceType::Rook | PieceType::Queen)
+        )
+}
+
+/// Finds the puzzles in the game with the default settings
+pub fn find_puzzles(tree: &GameTree) -> Vec<Puzzle> {
+    PuzzleFinder::new().find(tree)
+}
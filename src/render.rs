@@ -0,0 +1,150 @@
+use crate::moves::find_king;
+use crate::{Chess, Color, Piece, PieceType, Position};
+
+const LAST_MOVE_COLOR: &str = "\x1b[43m";
+const CHECK_COLOR: &str = "\x1b[41m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PieceStyle {
+    /// FEN letters, uppercase for white
+    Ascii,
+    /// Chess glyphs like ♔ and ♚
+    Unicode,
+}
+
+/// Draws a position as text, for terminals and logs
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardRenderer {
+    pub style: PieceStyle,
+    /// The color sitting at the bottom of the board
+    pub perspective: Color,
+    pub coordinates: bool,
+    /// Marks the squares of the last move with an ANSI background color
+    pub highlight_last_move: bool,
+    /// Marks a king in check with an ANSI background color
+    pub highlight_check: bool,
+}
+
+impl Default for BoardRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoardRenderer {
+    pub fn new() -> Self {
+        BoardRenderer {
+            style: PieceStyle::Ascii,
+            perspective: Color::White,
+            coordinates: true,
+            highlight_last_move: false,
+            highlight_check: false,
+        }
+    }
+
+    pub fn render(&self, chess: &Chess) -> String {
+        let (horizontal, top_left, top_right, bottom_left, bottom_right, vertical) =
+            match self.style {
+                PieceStyle::Ascii => ("-", "+", "+", "+", "+", "|"),
+                PieceStyle::Unicode => ("─", "┌", "┐", "└", "┘", "│"),
+            };
+        let margin = if self.coordinates { "  " } else { "" };
+        let border = horizontal.repeat(17);
+
+        let last_move = chess
+            .last_move()
+            .filter(|_| self.highlight_last_move)
+            .map(|m| [m.from, m.to]);
+        let check_square = chess
+            .is_check()
+            .filter(|_| self.highlight_check)
+            .and_then(|color| find_king(chess.board(), color));
+
+        let (rows, cols): (Vec<usize>, Vec<usize>) = match self.perspective {
+            Color::White => ((0..8).rev().collect(), (0..8).collect()),
+            Color::Black => ((0..8).collect(), (0..8).rev().collect()),
+        };
+
+        let mut out = String::new();
+        out.push_str(&format!("{}{}{}{}\n", margin, top_left, border, top_right));
+
+        for &y in rows.iter() {
+            if self.coordinates {
+                out.push_str(&format!("{} ", y + 1));
+            }
+            out.push_str(vertical);
+            out.push(' ');
+
+            for &x in cols.iter() {
+                let position = Position { x, y };
+                let symbol = match chess.piece_at(position) {
+                    Some(piece) => self.piece_symbol(piece),
+                    None => match self.style {
+                        PieceStyle::Ascii => '.',
+                        PieceStyle::Unicode => '·',
+                    },
+                };
+
+                let color = if check_square == Some(position) {
+                    Some(CHECK_COLOR)
+                } else if last_move.is_some_and(|m| m.contains(&position)) {
+                    Some(LAST_MOVE_COLOR)
+                } else {
+                    None
+                };
+
+                match color {
+                    Some(color) => out.push_str(&format!("{}{}{} ", color, symbol, RESET_COLOR)),
+                    None => out.push_str(&format!("{} ", symbol)),
+                }
+            }
+
+            out.push_str(vertical);
+            out.push('\n');
+        }
+
+        out.push_str(&format!(
+            "{}{}{}{}\n",
+            margin, bottom_left, border, bottom_right
+        ));
+
+        if self.coordinates {
+            out.push_str("    ");
+            let files: Vec<String> = cols
+                .iter()
+                .map(|&x| ((x as u8 + b'a') as char).to_string())
+                .collect();
+            out.push_str(&files.join(" "));
+            out.push('\n');
+        }
+
+        out
+    }
+
+    fn piece_symbol(&self, piece: &Piece) -> char {
+        match self.style {
+            PieceStyle::Ascii => piece.to_char(),
+            PieceStyle::Unicode => match (piece.color, piece.piece_type) {
+                (Color::White, PieceType::King) => '♔',
+                (Color::White, PieceType::Queen) => '♕',
+                (Color::White, PieceType::Rook) => '♖',
+                (Color::White, PieceType::Bishop) => '♗',
+                (Color::White, PieceType::Knight) => '♘',
+                (Color::White, PieceType::Pawn) => '♙',
+                (Color::Black, PieceType::King) => '♚',
+                (Color::Black, PieceType::Queen) => '♛',
+                (Color::Black, PieceType::Rook) => '♜',
+                (Color::Black, PieceType::Bishop) => '♝',
+                (Color::Black, PieceType::Knight) => '♞',
+                (Color::Black, PieceType::Pawn) => '♟',
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Chess {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", BoardRenderer::new().render(self))
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{CastlingType, Chess, Move, MoveType, PieceType, Position, Status};
+
+impl Chess {
+    /// Writes the move in standard algebraic notation, e.g. `Nbd7`, `exd5`, `O-O` or `e8=Q#`.
+    /// The move has to be valid in this position.
+    pub fn to_san(&self, mov: &Move) -> String {
+        let mut san = String::new();
+
+        match self.get_move_type(mov.from, mov.to) {
+            MoveType::Castling(CastlingType::KingSide(_)) => san.push_str("O-O"),
+            MoveType::Castling(CastlingType::QueenSide(_)) => san.push_str("O-O-O"),
+            _ => {
+                let piece_type = mov.piece.piece_type;
+                if piece_type == PieceType::Pawn {
+                    if mov.take_piece {
+                        san.push((mov.from.x as u8 + b'a') as char);
+                    }
+                } else {
+                    san.push(piece_type.to_char());
+                    san.push_str(&self.disambiguation(mov));
+                }
+
+                if mov.take_piece {
+                    san.push('x');
+                }
+                san.push_str(&mov.to.to_str());
+
+                if let Some(promotion) = mov.promotion {
+                    san.push('=');
+                    san.push(promotion.to_char());
+                }
+            }
+        }
+
+        let mut chess = self.clone();
+        if mov.promotion.is_none() && chess.get_move_type(mov.from, mov.to) == MoveType::Promotion {
+            // Without a promotion piece the move is shown as promoting to a queen
+            chess.move_piece(mov.from, mov.to);
+            chess.promote_piece(PieceType::Queen);
+        } else {
+            chess.play(mov);
+        }
+        match chess.status {
+            Status::Checkmate(_) => san.push('#'),
+            Status::Check(_) => san.push('+'),
+            _ => {}
+        }
+
+        san
+    }
+
+    // The file, rank or square needed to tell the move apart from moves by
+    // other pieces of the same type to the same square
+    fn disambiguation(&self, mov: &Move) -> String {
+        let others: Vec<Position> = self
+            .valid_moves
+            .iter()
+            .flatten()
+            .filter(|m| {
+                m.to == mov.to && m.from != mov.from && m.piece.piece_type == mov.piece.piece_type
+            })
+            .map(|m| m.from)
+            .collect();
+
+        if others.is_empty() {
+            return String::new();
+        }
+
+        let file = (mov.from.x as u8 + b'a') as char;
+        let rank = (mov.from.y as u8 + b'1') as char;
+
+        if others.iter().all(|p| p.x != mov.from.x) {
+            file.to_string()
+        } else if others.iter().all(|p| p.y != mov.from.y) {
+            rank.to_string()
+        } else {
+            format!("{}{}", file, rank)
+        }
+    }
+
+    /// Finds the valid move written in standard algebraic notation.
+    /// Check marks and annotations like `+`, `#`, `!` and `?` are ignored,
+    /// and castling can be written with both `O` and `0`.
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let moves = self.all_valid_moves();
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let king_side = san.len() == 3;
+            return moves
+                .into_iter()
+                .find(|m| match self.get_move_type(m.from, m.to) {
+                    MoveType::Castling(CastlingType::KingSide(_)) => king_side,
+                    MoveType::Castling(CastlingType::QueenSide(_)) => !king_side,
+                    _ => false,
+                });
+        }
+
+        let mut chars: Vec<char> = san.chars().collect();
+
+        let mut promotion = None;
+        if chars.len() > 2 {
+            if let Some(piece_type) = PieceType::from_char(chars[chars.len() - 1])
+                .filter(|_| chars[chars.len() - 1].is_ascii_uppercase())
+            {
+                promotion = Some(piece_type);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return None;
+        }
+        let to_str: String = chars[chars.len() - 2..].iter().collect();
+        let to = parse_square(&to_str)?;
+        chars.truncate(chars.len() - 2);
+
+        let piece_type = match chars.first() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let piece_type = PieceType::from_char(*c)?;
+                chars.remove(0);
+                piece_type
+            }
+            _ => PieceType::Pawn,
+        };
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in chars.iter() {
+            match c {
+                'a'..='h' => from_file = Some(*c as usize - 'a' as usize),
+                '1'..='8' => from_rank = Some(*c as usize - '1' as usize),
+                'x' | '-' | ':' => {}
+                _ => return None,
+            }
+        }
+
+        let mut candidates = moves.into_iter().filter(|m| {
+            m.to == to
+                && m.piece.piece_type == piece_type
+                && m.promotion == promotion
+                && from_file.is_none_or(|x| m.from.x == x)
+                && from_rank.is_none_or(|y| m.from.y == y)
+                && !matches!(self.get_move_type(m.from, m.to), MoveType::Castling(_))
+        });
+
+        let mov = candidates.next()?;
+        if candidates.next().is_some() {
+            // Ambiguous
+            return None;
+        }
+        Some(mov)
+    }
+}
+
+/// Parses a square like `e4`, unlike `Position::from_str` it doesn't panic on bad input
+pub fn parse_square(s: &str) -> Option<Position> {
+    let bytes = s.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1])
+    {
+        return None;
+    }
+    Some(Position {
+        x: (bytes[0] - b'a') as usize,
+        y: (bytes[1] - b'1') as usize,
+    })
+}
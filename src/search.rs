@@ -0,0 +1,462 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::eval::{evaluate, piece_value, MATE_SCORE};
+use crate::syzygy::Tablebase;
+use crate::{Chess, Move, PieceType, Status};
+
+/// Scores above this are mates, the distance to mate is `MATE_SCORE - score.abs()` plies
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+
+const MAX_PLY: usize = 64;
+
+/// The deepest search that makes sense, used when only time should stop the search
+pub const MAX_DEPTH: u8 = MAX_PLY as u8;
+
+/// When to stop searching. The search always finishes depth 1 so there is a move to play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: u8,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            depth: 4,
+            nodes: None,
+            time: None,
+        }
+    }
+}
+
+impl SearchLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub nodes: u64,
+    /// Nodes searched in the quiescence search, included in `nodes`
+    pub quiescence_nodes: u64,
+    pub tt_hits: u64,
+    pub beta_cutoffs: u64,
+    pub time: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// `None` if the game is already over
+    pub best_move: Option<Move>,
+    /// Centipawns from the point of view of the side to move
+    pub score: i32,
+    /// The expected line of play, starting with the best move
+    pub pv: Vec<Move>,
+    /// The last fully searched depth
+    pub depth: u8,
+    pub stats: SearchStats,
+}
+
+impl SearchResult {
+    /// The number of moves until mate, negative if the side to move gets mated
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score.abs() < MATE_THRESHOLD {
+            return None;
+        }
+        let plies = MATE_SCORE - self.score.abs();
+        let moves = (plies + 1) / 2;
+        Some(if self.score > 0 { moves } else { -moves })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Debug)]
+struct TtEntry {
+    depth: i32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+pub(crate) fn same_move(a: &Move, b: &Move) -> bool {
+    a.from == b.from && a.to == b.to && a.promotion == b.promotion
+}
+
+pub(crate) fn is_capture(chess: &Chess, mov: &Move) -> bool {
+    // Castling is written as the king taking its own rook, which is not a capture
+    mov.take_piece
+        && chess
+            .piece_at(mov.to)
+            .is_none_or(|p| p.color != mov.piece.color)
+}
+
+/// A hash of everything that makes positions equal: pieces, turn, castling and en passant
+pub fn position_hash(chess: &Chess) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for piece in chess.board().iter().flatten() {
+        (
+            piece.position.to_index(),
+            piece.piece_type as u8,
+            piece.color as i8,
+        )
+            .hash(&mut hasher);
+    }
+    (chess.side_to_move() as i8).hash(&mut hasher);
+    chess.castling_rights().to_fen().hash(&mut hasher);
+    chess.en_passant().map(|p| p.to_index()).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// An alpha-beta searcher. The transposition table and move ordering
+/// statistics are kept between searches, so reuse it within a game.
+#[derive(Clone, Debug)]
+pub struct Searcher {
+    tt: HashMap<u64, TtEntry>,
+    /// Entries in the transposition table before it is cleared
+    pub tt_size: usize,
+    killers: Vec<[Option<Move>; 2]>,
+    history: Vec<[i32; 64]>,
+    stats: SearchStats,
+    limits: SearchLimits,
+    start: Instant,
+    stopped: bool,
+    // Limits are ignored while searching depth 1
+    must_finish: bool,
+    stop_signal: Option<Arc<AtomicBool>>,
+    /// Endgame tables. With few enough pieces only the moves keeping the best result are searched.
+    pub tablebase: Option<Arc<Tablebase>>,
+    root_moves: Option<Vec<Move>>,
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Searcher {
+    pub fn new() -> Self {
+        Searcher {
+            tt: HashMap::new(),
+            tt_size: 1 << 20,
+            killers: vec![[None, None]; MAX_PLY],
+            history: vec![[0; 64]; 64],
+            stats: SearchStats::default(),
+            limits: SearchLimits::default(),
+            start: Instant::now(),
+            stopped: false,
+            must_finish: false,
+            stop_signal: None,
+            tablebase: None,
+            root_moves: None,
+        }
+    }
+
+    /// Lets another thread stop the search by setting the flag, like a time limit running out.
+    /// The searcher never resets the flag itself.
+    pub fn set_stop_signal(&mut self, signal: Arc<AtomicBool>) {
+        self.stop_signal = Some(signal);
+    }
+
+    /// Forgets everything learned in earlier searches, e.g. before a new game
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.killers = vec![[None, None]; MAX_PLY];
+        self.history = vec![[0; 64]; 64];
+    }
+
+    /// Searches the position with iterative deepening until a limit is reached
+    pub fn search(&mut self, chess: &Chess, limits: &SearchLimits) -> SearchResult {
+        self.search_with_info(chess, limits, |_| {})
+    }
+
+    /// Like `search`, calling `info` with the result of every finished depth
+    pub fn search_with_info(
+        &mut self,
+        chess: &Chess,
+        limits: &SearchLimits,
+        mut info: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        self.limits = *limits;
+        self.start = Instant::now();
+        self.stopped = false;
+        self.stats = SearchStats::default();
+        if self.tt.len() > self.tt_size {
+            self.tt.clear();
+        }
+        self.root_moves = self.tablebase.as_ref().and_then(|tb| tb.root_moves(chess));
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: evaluate(chess),
+            pv: Vec::new(),
+            depth: 0,
+            stats: self.stats,
+        };
+
+        for depth in 1..=limits.depth.max(1) {
+            self.must_finish = depth == 1;
+            let mut pv = Vec::new();
+            let score = self.negamax(chess, depth as i32, 0, -MATE_SCORE, MATE_SCORE, &mut pv);
+
+            // An unfinished depth can't be trusted
+            if self.stopped {
+                break;
+            }
+
+            result.score = score;
+            result.best_move = pv.first().cloned();
+            result.pv = pv;
+            result.depth = depth;
+            self.stats.time = self.start.elapsed();
+            result.stats = self.stats;
+            info(&result);
+
+            if result.best_move.is_none() || score.abs() >= MATE_THRESHOLD {
+                break;
+            }
+        }
+
+        self.stats.time = self.start.elapsed();
+        result.stats = self.stats;
+        result
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if self.must_finish {
+            return false;
+        }
+        if self.limits.nodes.is_some_and(|n| self.stats.nodes >= n)
+            || self.limits.time.is_some_and(|t| self.start.elapsed() >= t)
+            || self
+                .stop_signal
+                .as_ref()
+                .is_some_and(|s| s.load(Ordering::Relaxed))
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn negamax(
+        &mut self,
+        chess: &Chess,
+        depth: i32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if self.out_of_time() {
+            return 0;
+        }
+        self.stats.nodes += 1;
+
+        match chess.status() {
+            Status::Checkmate(_) => return -MATE_SCORE + ply as i32,
+            Status::Draw(_) => return 0,
+            _ => {}
+        }
+
+        if depth <= 0 || ply >= MAX_PLY {
+            return self.quiescence(chess, ply, alpha, beta);
+        }
+
+        let hash = position_hash(chess);
+        let mut tt_move = None;
+        if let Some(entry) = self.tt.get(&hash) {
+            tt_move = entry.best_move.clone();
+            if ply > 0 && entry.depth >= depth {
+                self.stats.tt_hits += 1;
+                let score = score_from_tt(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    if let Some(mov) = tt_move {
+                        pv.push(mov);
+                    }
+                    return score;
+                }
+            }
+        }
+
+        let mut moves = chess.all_valid_moves();
+        if let Some(root_moves) = self.root_moves.as_ref().filter(|_| ply == 0) {
+            moves.retain(|m| root_moves.iter().any(|r| same_move(r, m)));
+        }
+        self.order_moves(chess, &mut moves, tt_move.as_ref(), ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -MATE_SCORE;
+        let mut best_move = None;
+
+        for mov in moves.iter() {
+            let mut child = chess.clone();
+            child.play(mov);
+
+            let mut child_pv = Vec::new();
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mov.clone());
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mov.clone());
+                    pv.append(&mut child_pv);
+                }
+            }
+
+            if alpha >= beta {
+                self.stats.beta_cutoffs += 1;
+                if !is_capture(chess, mov) {
+                    let killers = &mut self.killers[ply];
+                    if killers[0].as_ref().is_none_or(|k| !same_move(k, mov)) {
+                        killers[1] = killers[0].take();
+                        killers[0] = Some(mov.clone());
+                    }
+                    self.history[mov.from.to_index()][mov.to.to_index()] += depth * depth;
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.insert(
+            hash,
+            TtEntry {
+                depth,
+                score: score_to_tt(best_score, ply),
+                bound,
+                best_move,
+            },
+        );
+
+        best_score
+    }
+
+    // Only searches captures and promotions so the evaluation isn't done in the middle of an exchange
+    fn quiescence(&mut self, chess: &Chess, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.out_of_time() {
+            return 0;
+        }
+        self.stats.nodes += 1;
+        self.stats.quiescence_nodes += 1;
+
+        let stand_pat = evaluate(chess);
+        if stand_pat.abs() >= MATE_THRESHOLD {
+            return if stand_pat > 0 {
+                MATE_SCORE - ply as i32
+            } else {
+                -MATE_SCORE + ply as i32
+            };
+        }
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        // Captures that lose material in the exchange are not worth looking at
+        let mut moves: Vec<Move> = chess
+            .all_valid_moves()
+            .into_iter()
+            .filter(|m| is_capture(chess, m) || m.promotion == Some(PieceType::Queen))
+            .filter(|m| chess.see(m) >= 0)
+            .collect();
+        self.order_moves(chess, &mut moves, None, ply);
+
+        for mov in moves.iter() {
+            let mut child = chess.clone();
+            child.play(mov);
+            let score = -self.quiescence(&child, ply + 1, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    fn order_moves(&self, chess: &Chess, moves: &mut [Move], tt_move: Option<&Move>, ply: usize) {
+        let killers = self.killers.get(ply);
+        moves.sort_by_cached_key(|mov| {
+            let score = if tt_move.is_some_and(|m| same_move(m, mov)) {
+                1_000_000
+            } else if is_capture(chess, mov) {
+                // Most valuable victim, least valuable attacker. En passant has no piece on the target.
+                let victim = chess
+                    .piece_at(mov.to)
+                    .map_or(PieceType::Pawn, |p| p.piece_type);
+                100_000 + piece_value(victim) * 10 - piece_value(mov.piece.piece_type) / 10
+            } else if mov.promotion.is_some() {
+                90_000 + mov.promotion.map_or(0, piece_value)
+            } else if killers.is_some_and(|k| k[0].as_ref().is_some_and(|k| same_move(k, mov))) {
+                80_000
+            } else if killers.is_some_and(|k| k[1].as_ref().is_some_and(|k| same_move(k, mov))) {
+                70_000
+            } else {
+                self.history[mov.from.to_index()][mov.to.to_index()].min(60_000)
+            };
+            -score
+        });
+    }
+}
+
+// Mate scores are stored relative to the node so they stay right when reached at another ply
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score + ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score - ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Searches the position with a new `Searcher`
+pub fn search(chess: &Chess, limits: &SearchLimits) -> SearchResult {
+    Searcher::new().search(chess, limits)
+}
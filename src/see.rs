@@ -0,0 +1,92 @@
+use crate::eval::piece_value;
+use crate::moves::attackers;
+use crate::{Chess, Color, Move, MoveType, Piece, PieceType, Position};
+
+fn promotes(piece: &Piece, to: Position) -> bool {
+    piece.piece_type == PieceType::Pawn
+        && ((piece.color == Color::White && to.y == 7)
+            || (piece.color == Color::Black && to.y == 0))
+}
+
+impl Chess {
+    /// Static exchange evaluation: the material the side to move wins, in centipawns,
+    /// if both sides keep capturing on the target square of the move with their least
+    /// valuable piece for as long as it pays off. Pieces behind others on the same line
+    /// join in when the ones in front are gone. Pins are not taken into account.
+    pub fn see(&self, mov: &Move) -> i32 {
+        let move_type = self.get_move_type(mov.from, mov.to);
+        if let MoveType::Castling(_) = move_type {
+            return 0;
+        }
+        let Some(piece) = self.piece_at(mov.from) else {
+            return 0;
+        };
+
+        let mut board = self.board().clone();
+        let to = mov.to;
+        let mut gain = match move_type {
+            MoveType::EnPassant => {
+                board[Position {
+                    x: to.x,
+                    y: mov.from.y,
+                }
+                .to_index()] = None;
+                piece_value(PieceType::Pawn)
+            }
+            _ => board[to.to_index()]
+                .as_ref()
+                .map_or(0, |p| piece_value(p.piece_type)),
+        };
+
+        // The piece standing on the square, which the next capture takes
+        let mut on_square = piece.clone();
+        if promotes(&on_square, to) {
+            let promotion = mov.promotion.unwrap_or(PieceType::Queen);
+            gain += piece_value(promotion) - piece_value(PieceType::Pawn);
+            on_square.piece_type = promotion;
+        }
+        board[mov.from.to_index()] = None;
+        on_square.position = to;
+        board[to.to_index()] = Some(on_square.clone());
+
+        // What each capture would win if the exchange stopped right after it
+        let mut gains = vec![gain];
+        let mut side = !piece.color;
+        loop {
+            let capturer = attackers(&board, to, side)
+                .into_iter()
+                .filter_map(|square| board[square.to_index()].clone())
+                .min_by_key(|p| match p.piece_type {
+                    PieceType::King => i32::MAX,
+                    piece_type => piece_value(piece_type),
+                });
+            let Some(mut capturer) = capturer else {
+                break;
+            };
+            // The king can only take when nothing takes it back
+            if capturer.piece_type == PieceType::King && !attackers(&board, to, !side).is_empty() {
+                break;
+            }
+
+            let mut gain = piece_value(on_square.piece_type) - gains.last().unwrap();
+            board[capturer.position.to_index()] = None;
+            if promotes(&capturer, to) {
+                gain += piece_value(PieceType::Queen) - piece_value(PieceType::Pawn);
+                capturer.piece_type = PieceType::Queen;
+            }
+            capturer.position = to;
+            board[to.to_index()] = Some(capturer.clone());
+            gains.push(gain);
+            on_square = capturer;
+            side = !side;
+        }
+
+        // Each side stops capturing when going on would lose more
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.last_mut().unwrap();
+            *previous = -(-*previous).max(last);
+        }
+        gains[0]
+    }
+}
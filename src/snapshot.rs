@@ -0,0 +1,130 @@
+use crate::{CastlingRights, Chess, Color, Move, Piece, Position, PositionIssue, Status};
+
+/// The complete state of a game as plain data, including what is needed for
+/// the repetition rules. With the `serde` feature it can be serialized and
+/// restored with `Chess::from_snapshot` to continue the same game.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChessSnapshot {
+    /// The 64 squares, indexed like `Chess::board`
+    pub board: Vec<Option<Piece>>,
+    pub turn: Color,
+    pub status: Status,
+    pub winner: Option<Color>,
+    pub awaiting_promotion_piece: Option<Piece>,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<Position>,
+    pub halfmove_clock: u8,
+    pub fullmove_number: u16,
+    pub last_move: Option<Move>,
+    /// How many times each earlier position has occurred
+    pub repetitions: Vec<(String, u8)>,
+}
+
+fn on_board(position: Position) -> bool {
+    position.x < 8 && position.y < 8
+}
+
+impl Chess {
+    pub fn snapshot(&self) -> ChessSnapshot {
+        let mut repetitions: Vec<(String, u8)> = self
+            .prev_boards
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect();
+        // Keep the output stable so equal games give equal snapshots
+        repetitions.sort();
+
+        ChessSnapshot {
+            board: self.board.to_vec(),
+            turn: self.turn,
+            status: self.status,
+            winner: self.winner,
+            awaiting_promotion_piece: self.awaiting_promotion_piece.clone(),
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.counter_50_move_rule,
+            fullmove_number: self.fullmove_number,
+            last_move: self.last_move.clone(),
+            repetitions,
+        }
+    }
+
+    /// Restores a game from a snapshot. The position has to be legal, and the status,
+    /// winner and pending promotion in the snapshot have to match the ones the
+    /// position gives, so a changed snapshot can't load an inconsistent game.
+    pub fn from_snapshot(snapshot: ChessSnapshot) -> Result<Self, &'static str> {
+        if snapshot.board.len() != 64 {
+            return Err("Invalid board");
+        }
+
+        for (i, tile) in snapshot.board.iter().enumerate() {
+            if tile
+                .as_ref()
+                .is_some_and(|p| !on_board(p.position) || p.position.to_index() != i)
+            {
+                return Err("Invalid board");
+            }
+        }
+
+        if snapshot.en_passant.is_some_and(|p| !on_board(p)) {
+            return Err("Invalid en passant square");
+        }
+        if snapshot
+            .last_move
+            .as_ref()
+            .is_some_and(|m| !on_board(m.from) || !on_board(m.to))
+        {
+            return Err("Invalid last move");
+        }
+
+        let board: [Option<Piece>; 64] = snapshot.board.try_into().unwrap();
+
+        let mut chess = Chess {
+            board,
+            turn: snapshot.turn,
+            status: Status::Chilling,
+            winner: None,
+            awaiting_promotion_piece: None,
+            valid_moves: std::array::from_fn(|_| Vec::new()),
+            castling_rights: snapshot.castling_rights,
+            en_passant: snapshot.en_passant,
+            counter_50_move_rule: snapshot.halfmove_clock,
+            fullmove_number: snapshot.fullmove_number.max(1),
+            last_move: snapshot.last_move,
+            prev_boards: snapshot.repetitions.into_iter().collect(),
+        };
+
+        // The current position was counted when the snapshot was taken, and is
+        // counted again by `update`. Only a pending promotion is not counted.
+        if chess.check_for_promotion().is_none() {
+            match chess.prev_boards.get_mut(&chess.position_key()) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => return Err("Invalid repetitions"),
+            }
+        }
+        chess.update(false);
+
+        // The pawn waiting to be promoted is the one pawn allowed on the last rank
+        let pending = chess
+            .awaiting_promotion_piece
+            .as_ref()
+            .map(|p| PositionIssue::PawnOnBackRank(p.position));
+        if chess
+            .validate_position()
+            .into_iter()
+            .any(|issue| Some(issue) != pending)
+        {
+            return Err("Illegal position");
+        }
+
+        if chess.status != snapshot.status
+            || chess.winner != snapshot.winner
+            || chess.awaiting_promotion_piece != snapshot.awaiting_promotion_piece
+        {
+            return Err("Status does not match the position");
+        }
+
+        Ok(chess)
+    }
+}
@@ -0,0 +1,302 @@
+use crate::moves::find_king;
+use crate::{Chess, Color, Piece, PieceType, Position};
+
+// Piece shapes drawn on a 45x45 grid. FILL and DETAIL are replaced with the
+// body and the line colors of the piece.
+const PAWN: &str = r#"<circle cx="22.5" cy="13" r="5" fill="FILL"/><path d="M16 35 L18.5 24 Q22.5 17 26.5 24 L29 35 Z" fill="FILL"/><rect x="11" y="34" width="23" height="5" fill="FILL"/>"#;
+const ROOK: &str = r#"<rect x="9" y="34" width="27" height="5" fill="FILL"/><rect x="13" y="16" width="19" height="18" fill="FILL"/><path d="M11 9h4v3h5V9h5v3h5V9h4v7H11z" fill="FILL"/><path d="M13 16h19M13 31h19" stroke="DETAIL" fill="none"/>"#;
+const BISHOP: &str = r#"<rect x="11" y="35" width="23" height="4" fill="FILL"/><path d="M15 35 Q12 25 22.5 13 Q33 25 30 35 Z" fill="FILL"/><circle cx="22.5" cy="10" r="2.5" fill="FILL"/><path d="M22.5 20v8M18.5 24h8" stroke="DETAIL" fill="none"/>"#;
+const KNIGHT: &str = r#"<path d="M12 39h22v-4c0-9-2-15-7-19l1-6-4 2-3-3-2 4c-4 2-8 7-9 12 0 2 2 3 4 2l5-3c1 2 1 4-1 6-4 4-6 6-6 9z" fill="FILL"/><circle cx="18" cy="15" r="1.2" fill="DETAIL" stroke="none"/>"#;
+const QUEEN: &str = r#"<rect x="10" y="35" width="25" height="4" fill="FILL"/><path d="M11 35 L8 13 L13 24 L15 10 L19 24 L22.5 9 L26 24 L30 10 L32 24 L37 13 L34 35 Z" fill="FILL"/><circle cx="8" cy="12" r="2" fill="FILL"/><circle cx="15" cy="9" r="2" fill="FILL"/><circle cx="22.5" cy="8" r="2" fill="FILL"/><circle cx="30" cy="9" r="2" fill="FILL"/><circle cx="37" cy="12" r="2" fill="FILL"/><path d="M12 31h21" stroke="DETAIL" fill="none"/>"#;
+const KING: &str = r#"<rect x="10" y="35" width="25" height="4" fill="FILL"/><path d="M12 35 Q7 23 15 19 Q22.5 16 22.5 23 Q22.5 16 30 19 Q38 23 33 35 Z" fill="FILL"/><path d="M21 6h3v4h3v3h-3v5h-3v-5h-3v-3h3z" fill="FILL"/><path d="M13 31h19" stroke="DETAIL" fill="none"/>"#;
+
+/// Colors used for the board, as any SVG color value
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgTheme {
+    pub light_square: String,
+    pub dark_square: String,
+    pub highlight: String,
+    pub arrow: String,
+    pub check: String,
+}
+
+impl SvgTheme {
+    pub fn brown() -> Self {
+        SvgTheme {
+            light_square: "#f0d9b5".to_string(),
+            dark_square: "#b58863".to_string(),
+            highlight: "#cdd26a".to_string(),
+            arrow: "#15781b".to_string(),
+            check: "#ff0000".to_string(),
+        }
+    }
+
+    pub fn blue() -> Self {
+        SvgTheme {
+            light_square: "#dee3e6".to_string(),
+            dark_square: "#8ca2ad".to_string(),
+            highlight: "#9bc700".to_string(),
+            arrow: "#003088".to_string(),
+            check: "#ff0000".to_string(),
+        }
+    }
+
+    pub fn green() -> Self {
+        SvgTheme {
+            light_square: "#ffffdd".to_string(),
+            dark_square: "#86a666".to_string(),
+            highlight: "#f6f669".to_string(),
+            arrow: "#882020".to_string(),
+            check: "#ff0000".to_string(),
+        }
+    }
+}
+
+impl Default for SvgTheme {
+    fn default() -> Self {
+        Self::brown()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arrow {
+    pub from: Position,
+    pub to: Position,
+    /// Uses the arrow color of the theme if not set
+    pub color: Option<String>,
+}
+
+impl Arrow {
+    pub fn new(from: Position, to: Position) -> Self {
+        Arrow {
+            from,
+            to,
+            color: None,
+        }
+    }
+}
+
+/// Draws a position as an SVG image, e.g. for puzzles and game reports
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgRenderer {
+    /// Width and height of the image in pixels
+    pub size: u32,
+    /// The color sitting at the bottom of the board
+    pub perspective: Color,
+    pub theme: SvgTheme,
+    pub coordinates: bool,
+    pub arrows: Vec<Arrow>,
+    pub highlighted: Vec<Position>,
+    /// Draws a red glow under a king in check
+    pub check_marker: bool,
+}
+
+impl Default for SvgRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SvgRenderer {
+    pub fn new() -> Self {
+        SvgRenderer {
+            size: 360,
+            perspective: Color::White,
+            theme: SvgTheme::default(),
+            coordinates: true,
+            arrows: Vec::new(),
+            highlighted: Vec::new(),
+            check_marker: true,
+        }
+    }
+
+    // Top left corner of the square in the image
+    fn square_origin(&self, position: Position) -> (f64, f64) {
+        let square_size = self.size as f64 / 8.0;
+        let (col, row) = match self.perspective {
+            Color::White => (position.x, 7 - position.y),
+            Color::Black => (7 - position.x, position.y),
+        };
+        (col as f64 * square_size, row as f64 * square_size)
+    }
+
+    fn square_center(&self, position: Position) -> (f64, f64) {
+        let half = self.size as f64 / 16.0;
+        let (x, y) = self.square_origin(position);
+        (x + half, y + half)
+    }
+
+    pub fn render(&self, chess: &Chess) -> String {
+        let size = self.size;
+        let square_size = size as f64 / 8.0;
+
+        let mut out = String::new();
+        out.push_str(&format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {size} {size}">"#
+        ));
+        out.push_str(&format!(
+            r#"<defs><radialGradient id="check-gradient"><stop offset="0%" stop-color="{}" stop-opacity="1"/><stop offset="100%" stop-color="{}" stop-opacity="0"/></radialGradient></defs>"#,
+            self.theme.check, self.theme.check
+        ));
+
+        for index in 0..64 {
+            let position = Position::from_index(index);
+            let (x, y) = self.square_origin(position);
+            let light = (position.x + position.y) % 2 == 1;
+            let color = if light {
+                &self.theme.light_square
+            } else {
+                &self.theme.dark_square
+            };
+            out.push_str(&format!(
+                r#"<rect x="{x}" y="{y}" width="{square_size}" height="{square_size}" fill="{color}"/>"#
+            ));
+            if self.highlighted.contains(&position) {
+                out.push_str(&format!(
+                    r#"<rect class="highlight" x="{x}" y="{y}" width="{square_size}" height="{square_size}" fill="{}" fill-opacity="0.6"/>"#,
+                    self.theme.highlight
+                ));
+            }
+        }
+
+        if self.coordinates {
+            out.push_str(&self.render_coordinates());
+        }
+
+        if self.check_marker {
+            if let Some(king) = chess
+                .is_check()
+                .and_then(|color| find_king(chess.board(), color))
+            {
+                let (x, y) = self.square_origin(king);
+                out.push_str(&format!(
+                    r#"<rect class="check" x="{x}" y="{y}" width="{square_size}" height="{square_size}" fill="url(#check-gradient)"/>"#
+                ));
+            }
+        }
+
+        for piece in chess.board().iter().flatten() {
+            out.push_str(&self.render_piece(piece));
+        }
+
+        for arrow in self.arrows.iter() {
+            out.push_str(&self.render_arrow(arrow));
+        }
+
+        out.push_str("</svg>");
+        out
+    }
+
+    fn render_coordinates(&self) -> String {
+        let square_size = self.size as f64 / 8.0;
+        let font_size = square_size / 5.0;
+        let mut out = String::new();
+
+        for i in 0..8 {
+            // Files along the bottom edge, ranks along the left edge
+            let file = Position {
+                x: i,
+                y: if self.perspective == Color::White {
+                    0
+                } else {
+                    7
+                },
+            };
+            let rank = Position {
+                x: if self.perspective == Color::White {
+                    0
+                } else {
+                    7
+                },
+                y: i,
+            };
+
+            let (x, y) = self.square_origin(file);
+            let color = self.coordinate_color(file);
+            out.push_str(&format!(
+                r#"<text x="{}" y="{}" font-size="{font_size}" font-family="sans-serif" fill="{color}" text-anchor="end">{}</text>"#,
+                x + square_size - font_size / 3.0,
+                y + square_size - font_size / 3.0,
+                (b'a' + i as u8) as char
+            ));
+
+            let (x, y) = self.square_origin(rank);
+            let color = self.coordinate_color(rank);
+            out.push_str(&format!(
+                r#"<text x="{}" y="{}" font-size="{font_size}" font-family="sans-serif" fill="{color}">{}</text>"#,
+                x + font_size / 3.0,
+                y + font_size * 1.1,
+                i + 1
+            ));
+        }
+
+        out
+    }
+
+    // Coordinates use the color of the other squares so they stay readable
+    fn coordinate_color(&self, position: Position) -> &str {
+        if (position.x + position.y) % 2 == 1 {
+            &self.theme.dark_square
+        } else {
+            &self.theme.light_square
+        }
+    }
+
+    fn render_piece(&self, piece: &Piece) -> String {
+        let shape = match piece.piece_type {
+            PieceType::King => KING,
+            PieceType::Queen => QUEEN,
+            PieceType::Rook => ROOK,
+            PieceType::Bishop => BISHOP,
+            PieceType::Knight => KNIGHT,
+            PieceType::Pawn => PAWN,
+        };
+        let (fill, detail) = match piece.color {
+            Color::White => ("#ffffff", "#000000"),
+            Color::Black => ("#000000", "#ffffff"),
+        };
+        let (x, y) = self.square_origin(piece.position);
+        let scale = self.size as f64 / 8.0 / 45.0;
+
+        format!(
+            r##"<g class="piece {} {}" transform="translate({x},{y}) scale({scale})" stroke="#000000" stroke-width="1.5" stroke-linejoin="round">{}</g>"##,
+            piece.color,
+            piece.piece_type,
+            shape.replace("FILL", fill).replace("DETAIL", detail)
+        )
+    }
+
+    fn render_arrow(&self, arrow: &Arrow) -> String {
+        let square_size = self.size as f64 / 8.0;
+        let color = arrow.color.as_ref().unwrap_or(&self.theme.arrow);
+        let (x1, y1) = self.square_center(arrow.from);
+        let (x2, y2) = self.square_center(arrow.to);
+
+        let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+        if length == 0.0 {
+            // An arrow onto its own square is drawn as a ring
+            return format!(
+                r#"<circle class="arrow" cx="{x1}" cy="{y1}" r="{}" stroke="{color}" stroke-width="{}" fill="none" opacity="0.8"/>"#,
+                square_size * 0.42,
+                square_size / 12.0
+            );
+        }
+
+        let (dx, dy) = ((x2 - x1) / length, (y2 - y1) / length);
+        let head_length = square_size * 0.4;
+        let head_width = square_size * 0.3;
+        let width = square_size * 0.15;
+
+        // The line stops where the head begins so the tip stays sharp
+        let (bx, by) = (x2 - dx * head_length, y2 - dy * head_length);
+        let (px, py) = (-dy * head_width, dx * head_width);
+
+        format!(
+            r#"<g class="arrow" opacity="0.8"><line x1="{x1}" y1="{y1}" x2="{bx}" y2="{by}" stroke="{color}" stroke-width="{width}" stroke-linecap="round"/><polygon points="{x2},{y2} {},{} {},{}" fill="{color}"/></g>"#,
+            bx + px,
+            by + py,
+            bx - px,
+            by - py
+        )
+    }
+}
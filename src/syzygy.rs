@@ -0,0 +1,1159 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::{CastlingRights, Chess, Color, Move, PieceType, Status};
+
+/// The most pieces, kings included, any Syzygy table has
+pub const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+const DTZ_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+
+// Flags of a table, one set for every side and pawn file
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// Rank given to root moves that win or lose for sure
+const MAX_DTZ: i32 = 1 << 18;
+
+/// The result of a position with perfect play, for the side to move.
+/// A cursed win is a win that takes too long and is a draw by the fifty move rule,
+/// a blessed loss is the same for the other side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_i32(value: i32) -> Self {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+}
+
+// Index tables shared by all tables, see `indices`
+struct Indices {
+    // Squares below the a1-h8 diagonal to 0..28
+    map_b1h1h7: [u64; 64],
+    // Squares in the a1-d1-d4 triangle to 0..10, with the diagonal last
+    map_a1d1d4: [usize; 64],
+    // The 462 ways to place two kings with the first in the triangle
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; MAX_PIECES],
+    // Squares a2-h7 to 0..48, the leading pawn has the highest value
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+
+fn file_of(square: usize) -> usize {
+    square % 8
+}
+
+fn rank_of(square: usize) -> usize {
+    square / 8
+}
+
+// Negative below the a1-h8 diagonal, 0 on it and positive above
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+fn flip_file(square: usize) -> usize {
+    square ^ 7
+}
+
+fn flip_rank(square: usize) -> usize {
+    square ^ 56
+}
+
+fn kings_touch(a: usize, b: usize) -> bool {
+    file_of(a).abs_diff(file_of(b)) <= 1 && rank_of(a).abs_diff(rank_of(b)) <= 1
+}
+
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(|| {
+        let mut ind = Indices {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; MAX_PIECES],
+            lead_pawns_size: [[0; 4]; MAX_PIECES],
+        };
+
+        let mut code = 0;
+        for s in 0..64 {
+            if off_diagonal(s) < 0 {
+                ind.map_b1h1h7[s] = code;
+                code += 1;
+            }
+        }
+
+        let mut code = 0;
+        let mut diagonal = Vec::new();
+        for s in [0, 1, 2, 3, 8, 9, 10, 11, 16, 17, 18, 19, 24, 25, 26, 27] {
+            if off_diagonal(s) < 0 {
+                ind.map_a1d1d4[s] = code;
+                code += 1;
+            } else if off_diagonal(s) == 0 {
+                diagonal.push(s);
+            }
+        }
+        for s in diagonal {
+            ind.map_a1d1d4[s] = code;
+            code += 1;
+        }
+
+        // If the first king is on the diagonal the other can't be above it
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for idx in 0..10 {
+            // b1 is the only square of the triangle mapped to 0
+            for s1 in (0..28).filter(|s| ind.map_a1d1d4[*s] == idx && (idx != 0 || *s == 1)) {
+                for s2 in 0..64 {
+                    if kings_touch(s1, s2) || (off_diagonal(s1) == 0 && off_diagonal(s2) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        ind.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            ind.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        ind.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                ind.binomial[k][n] = if k > 0 { ind.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { ind.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // The pawn nearest the edge, and then the lowest, leads
+        let mut available = 47;
+        for lead_pawns in 1..MAX_PIECES - 1 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let s = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        ind.map_pawns[s] = available;
+                        ind.map_pawns[flip_file(s)] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    ind.lead_pawn_idx[lead_pawns][s] = idx;
+                    idx += ind.binomial[lead_pawns - 1][ind.map_pawns[s]];
+                }
+                ind.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        ind
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    fn extension(&self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+}
+
+// How a group of values is stored, one for every side and pawn file.
+// The offsets point into the data of the table.
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    pieces: [u8; MAX_PIECES],
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    block_size: usize,
+    span: u64,
+    sparse_index_size: usize,
+    blocks: usize,
+    block_length_size: usize,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u32>,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    map_idx: [usize; 4],
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(pos..pos + 8)?.try_into().ok()?))
+}
+
+// The symbols a symbol stands for are stored as a pair of 12 bit symbols
+fn btree_left(data: &[u8], btree: usize, sym: usize) -> Option<usize> {
+    let lr = data.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some((lr[1] as usize & 0xF) << 8 | lr[0] as usize)
+}
+
+fn btree_right(data: &[u8], btree: usize, sym: usize) -> Option<usize> {
+    let lr = data.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some((lr[2] as usize) << 4 | (lr[1] as usize) >> 4)
+}
+
+// How many values minus one the symbol stands for
+fn set_symlen(data: &[u8], d: &mut PairsData, sym: usize, visited: &mut [bool]) -> Option<u32> {
+    visited[sym] = true;
+    let right = btree_right(data, d.btree, sym)?;
+    if right == 0xFFF {
+        return Some(0);
+    }
+    let left = btree_left(data, d.btree, sym)?;
+
+    for child in [left, right] {
+        if child >= visited.len() {
+            return None;
+        }
+        if !visited[child] {
+            d.symlen[child] = set_symlen(data, d, child, visited)?;
+        }
+    }
+    Some(d.symlen[left] + d.symlen[right] + 1)
+}
+
+// A table file, read into memory the first time it is needed
+struct Table {
+    kind: TableKind,
+    data: Vec<u8>,
+    // White has the pieces written first in the file name
+    white_pieces: String,
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // Pawns of the leading color first
+    pawn_count: [usize; 2],
+    piece_count: usize,
+    // By side to move and pawn file
+    items: Vec<Vec<PairsData>>,
+    dtz_map: usize,
+}
+
+impl Table {
+    fn new(name: &str, kind: TableKind, data: Vec<u8>) -> Option<Table> {
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, c: char| side.chars().filter(|x| *x == c).count();
+        let pawns = [count(white, 'P'), count(black, 'P')];
+
+        // The side with fewer pawns leads, because it compresses better
+        let white_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+        let mut table = Table {
+            kind,
+            data,
+            white_pieces: white.to_string(),
+            symmetric: white == black,
+            has_pawns: pawns[0] + pawns[1] > 0,
+            has_unique_pieces: [white, black]
+                .iter()
+                .any(|side| "QRBNP".chars().any(|c| count(side, c) == 1)),
+            pawn_count: if white_leads {
+                pawns
+            } else {
+                [pawns[1], pawns[0]]
+            },
+            piece_count: white.len() + black.len(),
+            items: Vec::new(),
+            dtz_map: 0,
+        };
+        table.init()?;
+        Some(table)
+    }
+
+    fn sides(&self) -> usize {
+        if self.kind == TableKind::Wdl && !self.symmetric {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        &self.items[stm % self.sides()][if self.has_pawns { file } else { 0 }]
+    }
+
+    // Reads the layout of the file
+    fn init(&mut self) -> Option<()> {
+        let magic = match self.kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if self.data.get(0..4)? != magic || self.piece_count > MAX_PIECES {
+            return None;
+        }
+
+        let flags = *self.data.get(4)?;
+        if (flags & 2 != 0) != self.has_pawns || (flags & 1 != 0) == self.symmetric {
+            return None;
+        }
+        let mut pos = 5;
+
+        let sides = self.sides();
+        let files = if self.has_pawns { 4 } else { 1 };
+        // Pawns on both sides
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+        self.items = vec![vec![PairsData::default(); files]; sides];
+
+        for file in 0..files {
+            let first = *self.data.get(pos)?;
+            let second = if pp { *self.data.get(pos + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            pos += 1 + pp as usize;
+
+            for k in 0..self.piece_count {
+                let byte = *self.data.get(pos)?;
+                for (i, side) in self.items.iter_mut().enumerate() {
+                    side[file].pieces[k] = if i == 0 { byte & 0xF } else { byte >> 4 };
+                }
+                pos += 1;
+            }
+
+            for (i, order) in order.into_iter().enumerate().take(sides) {
+                let mut d = std::mem::take(&mut self.items[i][file]);
+                self.set_groups(&mut d, order, file);
+                self.items[i][file] = d;
+            }
+        }
+        pos += pos & 1;
+
+        for file in 0..files {
+            for i in 0..sides {
+                let mut d = std::mem::take(&mut self.items[i][file]);
+                pos = self.set_sizes(&mut d, pos)?;
+                self.items[i][file] = d;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            pos = self.set_dtz_map(pos, files)?;
+        }
+
+        for file in 0..files {
+            for side in self.items.iter_mut() {
+                side[file].sparse_index = pos;
+                pos += side[file].sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side in self.items.iter_mut() {
+                side[file].block_length = pos;
+                pos += side[file].block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            // The blocks start at multiples of 64 bytes
+            for side in self.items.iter_mut().filter(|side| side[file].blocks > 0) {
+                pos = (pos + 0x3F) & !0x3F;
+                side[file].data = pos;
+                pos += side[file].blocks * side[file].block_size;
+            }
+        }
+
+        (pos <= self.data.len()).then_some(())
+    }
+
+    // Pieces of the same kind are encoded together in groups. Without pawns the first
+    // group is three different pieces or the two kings, with pawns it is the leading pawns.
+    fn set_groups(&self, d: &mut PairsData, order: [u8; 2], file: usize) {
+        let ind = indices();
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        // The groups are multiplied together in the order stored in the file
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+        let mut idx = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    ind.lead_pawns_size[d.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                d.group_idx[1] = idx;
+                idx *= ind.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= ind.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        d.group_idx[n] = idx;
+    }
+
+    // Reads the sizes and the Huffman code of the values
+    fn set_sizes(&self, d: &mut PairsData, mut pos: usize) -> Option<usize> {
+        let data = &self.data;
+        d.flags = *data.get(pos)?;
+        pos += 1;
+
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            // The value every position has
+            d.min_sym_len = *data.get(pos)?;
+            return Some(pos + 1);
+        }
+
+        let groups = d.group_len.iter().position(|len| *len == 0)?;
+        let size = d.group_idx[groups];
+
+        d.block_size = 1 << *data.get(pos)?;
+        d.span = 1 << *data.get(pos + 1)?;
+        d.sparse_index_size = size.div_ceil(d.span) as usize;
+        let padding = *data.get(pos + 2)? as usize;
+        d.blocks = read_u32(data, pos + 3)? as usize;
+        d.block_length_size = d.blocks + padding;
+        let max_sym_len = *data.get(pos + 7)?;
+        d.min_sym_len = *data.get(pos + 8)?;
+        pos += 9;
+        if max_sym_len < d.min_sym_len || d.min_sym_len == 0 {
+            return None;
+        }
+
+        // A canonical Huffman code, where longer codes have lower values
+        d.lowest_sym = pos;
+        let lengths = (max_sym_len - d.min_sym_len + 1) as usize;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16(data, d.lowest_sym + 2 * i)? as u64;
+            let next = read_u16(data, d.lowest_sym + 2 * (i + 1))? as u64;
+            d.base64[i] = (d.base64[i + 1] + lowest).checked_sub(next)? / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            let shift = 64u32.checked_sub(i as u32 + d.min_sym_len as u32)?;
+            *base = base.checked_shl(shift).unwrap_or(0);
+        }
+        pos += lengths * 2;
+
+        let symbols = read_u16(data, pos)? as usize;
+        pos += 2;
+        d.btree = pos;
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                d.symlen[sym] = set_symlen(data, d, sym, &mut visited)?;
+            }
+        }
+
+        Some(pos + symbols * 3 + (symbols & 1))
+    }
+
+    // DTZ values are stored by how common they are, the map gives the real values
+    fn set_dtz_map(&mut self, mut pos: usize, files: usize) -> Option<usize> {
+        self.dtz_map = pos;
+        for file in 0..files {
+            let flags = self.items[0][file].flags;
+            if flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            if flags & FLAG_WIDE != 0 {
+                pos += pos & 1;
+                for i in 0..4 {
+                    self.items[0][file].map_idx[i] = (pos - self.dtz_map) / 2 + 1;
+                    pos += 2 * read_u16(&self.data, pos)? as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    self.items[0][file].map_idx[i] = pos - self.dtz_map + 1;
+                    pos += *self.data.get(pos)? as usize + 1;
+                }
+            }
+        }
+        Some(pos + (pos & 1))
+    }
+
+    // Finds the value with the index by decoding the block it is in
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> Option<usize> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as usize);
+        }
+        let data = &self.data;
+
+        // The sparse index points to the value in the middle of every span
+        let k = (idx / d.span) as usize;
+        if k >= d.sparse_index_size {
+            return None;
+        }
+        let mut block = read_u32(data, d.sparse_index + 6 * k)? as usize;
+        let mut offset = read_u16(data, d.sparse_index + 6 * k + 4)? as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: usize| -> Option<i64> {
+            if block >= d.block_length_size {
+                return None;
+            }
+            Some(read_u16(data, d.block_length + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + block * d.block_size;
+        let mut buf = read_u64_be(data, ptr)?;
+        ptr += 8;
+        let mut buf_size: u32 = 64;
+        let min_len = d.min_sym_len as u32;
+
+        let mut sym = loop {
+            let mut len = 0;
+            while buf < d.base64[len] {
+                len += 1;
+                if len >= d.base64.len() {
+                    return None;
+                }
+            }
+            let shift = 64u32.checked_sub(len as u32 + min_len)?;
+            let sym = ((buf - d.base64[len]).checked_shr(shift).unwrap_or(0)) as usize
+                + read_u16(data, d.lowest_sym + 2 * len)? as usize;
+            let values = *d.symlen.get(sym)? as i64 + 1;
+            if offset < values {
+                break sym;
+            }
+
+            offset -= values;
+            let len = len as u32 + min_len;
+            buf = buf.checked_shl(len).unwrap_or(0);
+            buf_size = buf_size.checked_sub(len)?;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (read_u32_be(data, ptr)? as u64) << (64 - buf_size);
+                ptr += 4;
+            }
+        };
+
+        // Symbols stand for pairs of symbols, find the value the offset is in
+        while d.symlen[sym] != 0 {
+            let left = btree_left(data, d.btree, sym)?;
+            let values = *d.symlen.get(left)? as i64 + 1;
+            if offset < values {
+                sym = left;
+            } else {
+                offset -= values;
+                sym = btree_right(data, d.btree, sym)?;
+            }
+        }
+
+        btree_left(data, d.btree, sym)
+    }
+
+    // The DTZ in plies from the stored value
+    fn map_score(&self, file: usize, value: usize, wdl: i32) -> Option<i32> {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let d = self.get(0, file);
+        let mut value = value;
+
+        if d.flags & FLAG_MAPPED != 0 {
+            let idx = d.map_idx[WDL_MAP[(wdl + 2) as usize]];
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16(&self.data, self.dtz_map + 2 * (idx + value))? as usize
+            } else {
+                *self.data.get(self.dtz_map + idx + value)? as usize
+            };
+        }
+
+        let mut value = value as i32;
+        if (wdl == 2 && d.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == -2 && d.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == 1
+            || wdl == -1
+        {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+
+    fn probe(&self, chess: &Chess, wdl: i32) -> Option<TableValue> {
+        let ind = indices();
+        let turn = (chess.side_to_move() == Color::Black) as usize;
+
+        // Only one side is stored for symmetric tables, and the stronger side
+        // is always white, so the colors and the board may have to be flipped
+        let flip =
+            (self.symmetric && turn == 1) || material(chess, Color::White) != self.white_pieces;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ turn;
+
+        let mut squares = Vec::with_capacity(MAX_PIECES);
+        let mut pieces = Vec::with_capacity(MAX_PIECES);
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        // With pawns there is a table for every file of the leading pawn.
+        // The table code of the pawn is compared with the board after flipping
+        let pawn = self.items[0][0].pieces[0];
+        let board_pawn = pawn ^ flip_color;
+        if self.has_pawns {
+            for (i, piece) in chess.board().iter().enumerate() {
+                if piece
+                    .as_ref()
+                    .is_some_and(|p| piece_code(p.piece_type, p.color) == board_pawn)
+                {
+                    squares.push(i ^ flip_squares);
+                    pieces.push(pawn);
+                }
+            }
+            lead_pawns = squares.len();
+
+            let lead = (0..lead_pawns).max_by_key(|i| ind.map_pawns[squares[*i]])?;
+            squares.swap(0, lead);
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+
+        // Symmetric tables without pawns look the same from both sides
+        let one_sided = self.has_pawns || !self.symmetric;
+        if self.kind == TableKind::Dtz
+            && one_sided
+            && (self.get(stm, file).flags & FLAG_STM) as usize != stm
+        {
+            return Some(TableValue::ChangeStm);
+        }
+
+        for (i, piece) in chess.board().iter().enumerate() {
+            if let Some(p) = piece {
+                let code = piece_code(p.piece_type, p.color);
+                if !(self.has_pawns && code == board_pawn) {
+                    squares.push(i ^ flip_squares);
+                    pieces.push(code ^ flip_color);
+                }
+            }
+        }
+        let size = squares.len();
+        if size != self.piece_count {
+            return None;
+        }
+
+        // Put the pieces in the order of the table
+        let d = self.get(stm, file);
+        for i in lead_pawns..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|j| pieces[*j] == d.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // The first piece goes in the a1-d1-d4 triangle
+        if file_of(squares[0]) > 3 {
+            for square in squares.iter_mut() {
+                *square = flip_file(*square);
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = ind.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|s| ind.map_pawns[*s]);
+            for (i, square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += ind.binomial[i][ind.map_pawns[*square]];
+            }
+        } else {
+            if rank_of(squares[0]) > 3 {
+                for square in squares.iter_mut() {
+                    *square = flip_rank(*square);
+                }
+            }
+
+            // The first piece of the leading group not on the diagonal goes below it
+            for i in 0..d.group_len[0] {
+                if off_diagonal(squares[i]) == 0 {
+                    continue;
+                }
+                if off_diagonal(squares[i]) > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let s = [squares[0], squares[1], squares[2]];
+                let adjust1 = (s[1] > s[0]) as u64;
+                let adjust2 = (s[2] > s[0]) as u64 + (s[2] > s[1]) as u64;
+                let rank = |s: usize| rank_of(s) as u64;
+
+                idx = if off_diagonal(s[0]) != 0 {
+                    (ind.map_a1d1d4[s[0]] as u64 * 63 + (s[1] as u64 - adjust1)) * 62 + s[2] as u64
+                        - adjust2
+                } else if off_diagonal(s[1]) != 0 {
+                    (6 * 63 + rank(s[0]) * 28 + ind.map_b1h1h7[s[1]]) * 62 + s[2] as u64 - adjust2
+                } else if off_diagonal(s[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(s[0]) * 7 * 28
+                        + (rank(s[1]) - adjust1) * 28
+                        + ind.map_b1h1h7[s[2]]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(s[0]) * 7 * 6
+                        + (rank(s[1]) - adjust1) * 6
+                        + (rank(s[2]) - adjust2)
+                };
+            } else {
+                idx = ind.map_kk[ind.map_a1d1d4[squares[0]]][squares[1]];
+            }
+        }
+
+        // The other groups, each sorted and counted as a combination of the free squares
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let group_end = group_start + d.group_len[next];
+            squares[group_start..group_end].sort();
+            let mut n = 0;
+            for i in 0..d.group_len[next] {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start]
+                    .iter()
+                    .filter(|s| square > **s)
+                    .count();
+                let free = (square - adjust).checked_sub(8 * remaining_pawns as usize)?;
+                n += ind.binomial[i + 1][free];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(d, idx)?;
+        match self.kind {
+            TableKind::Wdl => Some(TableValue::Value(value as i32 - 2)),
+            TableKind::Dtz => Some(TableValue::Value(self.map_score(file, value, wdl)?)),
+        }
+    }
+}
+
+enum TableValue {
+    Value(i32),
+    // DTZ tables only store one side to move
+    ChangeStm,
+}
+
+// The piece codes used in the table files
+fn piece_code(piece_type: PieceType, color: Color) -> u8 {
+    let code = match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+    if color == Color::White {
+        code
+    } else {
+        code + 8
+    }
+}
+
+// The pieces of one side like in the file names, e.g. `KRP`
+fn material(chess: &Chess, color: Color) -> String {
+    let mut pieces: Vec<(u8, char)> = chess
+        .board()
+        .iter()
+        .flatten()
+        .filter(|p| p.color == color)
+        .map(|p| match p.piece_type {
+            PieceType::King => (0, 'K'),
+            PieceType::Queen => (1, 'Q'),
+            PieceType::Rook => (2, 'R'),
+            PieceType::Bishop => (3, 'B'),
+            PieceType::Knight => (4, 'N'),
+            PieceType::Pawn => (5, 'P'),
+        })
+        .collect();
+    pieces.sort();
+    pieces.into_iter().map(|(_, c)| c).collect()
+}
+
+fn is_zeroing(chess: &Chess, mov: &Move) -> bool {
+    crate::search::is_capture(chess, mov) || mov.piece.piece_type == PieceType::Pawn
+}
+
+fn play(chess: &Chess, mov: &Move) -> Chess {
+    let mut child = chess.clone();
+    child.play(mov);
+    child
+}
+
+// The DTZ right before a zeroing move with the result
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+/// Syzygy endgame tablebases, probed for the result and the distance to zeroing
+/// (the next capture or pawn move) of positions with few pieces.
+/// Tables are read into memory the first time they are needed.
+#[derive(Default)]
+pub struct Tablebase {
+    files: HashMap<String, PathBuf>,
+    tables: Mutex<HashMap<String, Option<Arc<Table>>>>,
+    max_pieces: usize,
+}
+
+impl std::fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Tablebase")
+            .field("files", &self.files.len())
+            .field("max_pieces", &self.max_pieces)
+            .finish()
+    }
+}
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the `.rtbw` and `.rtbz` files in the directory
+    pub fn open<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        let mut tablebase = Tablebase::new();
+        tablebase.add_directory(dir)?;
+        Ok(tablebase)
+    }
+
+    /// Adds the tables in another directory, returns how many files were found
+    pub fn add_directory<P: AsRef<Path>>(&mut self, dir: P) -> std::io::Result<usize> {
+        let mut found = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let stem = match name
+                .strip_suffix(".rtbw")
+                .or_else(|| name.strip_suffix(".rtbz"))
+            {
+                Some(stem) => stem,
+                None => continue,
+            };
+            let valid = stem.split_once('v').is_some_and(|(w, b)| {
+                w.starts_with('K')
+                    && b.starts_with('K')
+                    && (w.len() + b.len()) <= MAX_PIECES
+                    && (w.chars().chain(b.chars())).all(|c| "KQRBNP".contains(c))
+            });
+            if valid {
+                self.max_pieces = self.max_pieces.max(stem.len() - 1);
+                self.files.insert(name, path);
+                found += 1;
+            }
+        }
+        Ok(found)
+    }
+
+    /// The most pieces of the tables found, 0 if there are none
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn table(&self, chess: &Chess, kind: TableKind) -> Option<Arc<Table>> {
+        let white = material(chess, Color::White);
+        let black = material(chess, Color::Black);
+        let name = [
+            format!("{}v{}", white, black),
+            format!("{}v{}", black, white),
+        ]
+        .into_iter()
+        .find(|name| {
+            self.files
+                .contains_key(&format!("{}.{}", name, kind.extension()))
+        })?;
+        let file = format!("{}.{}", name, kind.extension());
+
+        let mut tables = self.tables.lock().unwrap();
+        tables
+            .entry(file.clone())
+            .or_insert_with(|| {
+                let data = std::fs::read(&self.files[&file]).ok()?;
+                Table::new(&name, kind, data).map(Arc::new)
+            })
+            .clone()
+    }
+
+    fn probe_table(&self, chess: &Chess, kind: TableKind, wdl: i32) -> Option<TableValue> {
+        // The tables have no king against king
+        if chess.board().iter().flatten().count() == 2 {
+            return Some(TableValue::Value(0));
+        }
+        self.table(chess, kind)?.probe(chess, wdl)
+    }
+
+    // The tables may store anything for positions where a capture is best, since the
+    // generator knows they are looked at anyway. Returns the result and whether a
+    // zeroing move is best.
+    fn search(&self, chess: &Chess, zeroing_moves: bool) -> Option<(i32, bool)> {
+        let moves = chess.all_valid_moves();
+        if moves.is_empty() {
+            let mated = matches!(chess.status(), Status::Checkmate(_));
+            return Some((if mated { -2 } else { 0 }, false));
+        }
+
+        let mut best = -2;
+        let mut searched = 0;
+        for mov in moves.iter() {
+            let zeroing = if zeroing_moves {
+                is_zeroing(chess, mov)
+            } else {
+                crate::search::is_capture(chess, mov)
+            };
+            if !zeroing {
+                continue;
+            }
+            searched += 1;
+
+            let value = -self.search(&play(chess, mov), false)?.0;
+            if value > best {
+                best = value;
+                if value >= 2 {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // When every move was searched the stored value can't be trusted
+        let all_searched = searched == moves.len();
+        let value = if all_searched {
+            best
+        } else {
+            match self.probe_table(chess, TableKind::Wdl, 0)? {
+                TableValue::Value(value) => value,
+                TableValue::ChangeStm => return None,
+            }
+        };
+
+        if best >= value {
+            Some((best, best > 0 || all_searched))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    fn dtz(&self, chess: &Chess) -> Option<i32> {
+        let (wdl, zeroing) = self.search(chess, true)?;
+        if wdl == 0 {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        match self.probe_table(chess, TableKind::Dtz, wdl)? {
+            TableValue::Value(dtz) => {
+                let cursed = if wdl.abs() == 1 { 100 } else { 0 };
+                Some((dtz + cursed) * wdl.signum())
+            }
+            TableValue::ChangeStm => {
+                // The table has the other side to move, so look one move ahead
+                let mut min = i32::MAX;
+                for mov in chess.all_valid_moves() {
+                    let zeroing = is_zeroing(chess, &mov);
+                    let child = play(chess, &mov);
+                    let mut dtz = if zeroing {
+                        -dtz_before_zeroing(self.search(&child, false)?.0)
+                    } else {
+                        -self.dtz(&child)?
+                    };
+
+                    if dtz == 1 && matches!(child.status(), Status::Checkmate(_)) {
+                        min = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < min && dtz.signum() == wdl.signum() {
+                        min = dtz;
+                    }
+                }
+                Some(if min == i32::MAX { -1 } else { min })
+            }
+        }
+    }
+
+    // The position without its history, so probing can't end it by repetition
+    fn probe_position(&self, chess: &Chess) -> Option<Chess> {
+        let pieces = chess.board().iter().flatten().count();
+        let rights = chess.castling_rights();
+        if pieces > self.max_pieces.max(2)
+            || CastlingRights::ALL.iter().any(|c| rights.has(*c))
+            || chess.pending_promotion().is_some()
+        {
+            return None;
+        }
+
+        let mut fen: Vec<String> = chess.to_fen().split(' ').map(String::from).collect();
+        fen[4] = "0".to_string();
+        Chess::from_fen(&fen.join(" "), false).ok()
+    }
+
+    /// The result for the side to move, `None` if a table is missing or the position
+    /// has castling rights
+    pub fn probe_wdl(&self, chess: &Chess) -> Option<Wdl> {
+        let position = self.probe_position(chess)?;
+        Some(Wdl::from_i32(self.search(&position, false)?.0))
+    }
+
+    /// The distance to zeroing in plies, positive when the side to move wins and 0 for
+    /// draws. Cursed wins and blessed losses are more than 100 from zero.
+    /// Like the tables themselves the value can be one ply too high.
+    pub fn probe_dtz(&self, chess: &Chess) -> Option<i32> {
+        self.dtz(&self.probe_position(chess)?)
+    }
+
+    // Ranks the moves like the fifty move rule is played, higher is better
+    fn rank_moves(&self, chess: &Chess) -> Option<Vec<(Move, i32, i32)>> {
+        let position = self.probe_position(chess)?;
+        let halfmoves = chess.halfmove_clock() as i32;
+
+        let mut ranked = Vec::new();
+        for mov in position.all_valid_moves() {
+            let child = play(&position, &mov);
+            let mut dtz = if is_zeroing(&position, &mov) {
+                dtz_before_zeroing(-self.search(&child, false)?.0)
+            } else {
+                let dtz = -self.dtz(&child)?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && matches!(child.status(), Status::Checkmate(_)) {
+                dtz = 1;
+            }
+
+            // Wins and losses are all the same until the fifty move rule is close
+            let rank = if dtz > 0 {
+                if dtz + halfmoves <= 99 {
+                    MAX_DTZ
+                } else {
+                    MAX_DTZ - (dtz + halfmoves)
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + halfmoves < 100 {
+                    -MAX_DTZ
+                } else {
+                    -MAX_DTZ + (-dtz + halfmoves)
+                }
+            } else {
+                0
+            };
+            ranked.push((mov, rank, dtz));
+        }
+        Some(ranked)
+    }
+
+    /// The moves that keep the best result, counting the fifty move rule.
+    /// The search uses this to only look at these moves in tablebase positions.
+    pub fn root_moves(&self, chess: &Chess) -> Option<Vec<Move>> {
+        let ranked = self.rank_moves(chess)?;
+        let best = ranked.iter().map(|(_, rank, _)| *rank).max()?;
+        Some(
+            ranked
+                .into_iter()
+                .filter(|(_, rank, _)| *rank == best)
+                .map(|(mov, _, _)| mov)
+                .collect(),
+        )
+    }
+
+    /// The move that keeps the best result and wins as fast as possible, or loses as
+    /// slowly as possible, measured in distance to zeroing
+    pub fn best_move(&self, chess: &Chess) -> Option<Move> {
+        let ranked = self.rank_moves(chess)?;
+        ranked
+            .into_iter()
+            .max_by_key(|(_, rank, dtz)| (*rank, -*dtz))
+            .map(|(mov, _, _)| mov)
+    }
+}
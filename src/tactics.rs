@@ -0,0 +1,248 @@
+use crate::eval::piece_value;
+use crate::moves::{attackers, find_king};
+use crate::{Board, Chess, Color, Move, Piece, PieceType, Position};
+
+const STRAIGHT: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const DIAGONAL: [(i8, i8); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+/// A tactic on the board with the squares involved, e.g. to draw arrows from `attacker`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Motif {
+    /// One piece attacking several pieces it can win something from
+    Fork {
+        attacker: Position,
+        targets: Vec<Position>,
+    },
+    /// The pinned piece can't move without giving up the piece behind it.
+    /// Absolute when the piece behind is the king, so moving is not even allowed.
+    Pin {
+        attacker: Position,
+        pinned: Position,
+        behind: Position,
+        absolute: bool,
+    },
+    /// The piece in front has to move away and leave the less valuable piece behind it
+    Skewer {
+        attacker: Position,
+        front: Position,
+        behind: Position,
+    },
+    /// Moving a piece opened the line from `attacker` to `target`
+    DiscoveredAttack {
+        attacker: Position,
+        target: Position,
+    },
+    /// Moving a piece opened the line from `attacker` to the king
+    DiscoveredCheck { attacker: Position, king: Position },
+    DoubleCheck {
+        king: Position,
+        checkers: Vec<Position>,
+    },
+    /// The king can't leave its first rank, so a rook or queen reaching it mates
+    BackRankWeakness { king: Position },
+}
+
+fn directions(piece_type: PieceType) -> Vec<(i8, i8)> {
+    match piece_type {
+        PieceType::Rook => STRAIGHT.to_vec(),
+        PieceType::Bishop => DIAGONAL.to_vec(),
+        PieceType::Queen => [STRAIGHT, DIAGONAL].concat(),
+        _ => Vec::new(),
+    }
+}
+
+// The first two pieces seen from the square in the direction
+fn first_two(board: &Board, from: Position, (dx, dy): (i8, i8)) -> Vec<&Piece> {
+    let mut pieces = Vec::new();
+    let (mut x, mut y) = (from.x as i8 + dx, from.y as i8 + dy);
+    while (0..8).contains(&x) && (0..8).contains(&y) && pieces.len() < 2 {
+        if let Some(p) = board[(y * 8 + x) as usize].as_ref() {
+            pieces.push(p);
+        }
+        x += dx;
+        y += dy;
+    }
+    pieces
+}
+
+// Whether the square lies strictly between the two others on a straight or diagonal line
+fn is_between(square: Position, from: Position, to: Position) -> bool {
+    let (dx, dy) = (to.x as i8 - from.x as i8, to.y as i8 - from.y as i8);
+    if dx != 0 && dy != 0 && dx.abs() != dy.abs() {
+        return false;
+    }
+    let (step_x, step_y) = (dx.signum(), dy.signum());
+    let (mut x, mut y) = (from.x as i8 + step_x, from.y as i8 + step_y);
+    while (x, y) != (to.x as i8, to.y as i8) {
+        if (x, y) == (square.x as i8, square.y as i8) {
+            return true;
+        }
+        x += step_x;
+        y += step_y;
+    }
+    false
+}
+
+// Attacking the target wins something: it is the king, worth more than the attacker or undefended
+fn threatens(board: &Board, attacker: &Piece, target: &Piece) -> bool {
+    target.piece_type == PieceType::King
+        || piece_value(target.piece_type) > piece_value(attacker.piece_type)
+        || attackers(board, target.position, target.color).is_empty()
+}
+
+/// Pins and skewers by the sliding pieces of the color
+pub fn pins_and_skewers(chess: &Chess, color: Color) -> Vec<Motif> {
+    line_motifs(chess.board(), color)
+}
+
+fn line_motifs(board: &Board, color: Color) -> Vec<Motif> {
+    let mut motifs = Vec::new();
+    for piece in board.iter().flatten().filter(|p| p.color == color) {
+        for direction in directions(piece.piece_type) {
+            let [front, behind] = first_two(board, piece.position, direction)[..] else {
+                continue;
+            };
+            if front.color == color || behind.color == color {
+                continue;
+            }
+
+            if front.piece_type == PieceType::King
+                || (behind.piece_type != PieceType::King
+                    && piece_value(front.piece_type) > piece_value(behind.piece_type))
+            {
+                motifs.push(Motif::Skewer {
+                    attacker: piece.position,
+                    front: front.position,
+                    behind: behind.position,
+                });
+            } else if behind.piece_type == PieceType::King
+                || piece_value(behind.piece_type) > piece_value(front.piece_type)
+            {
+                motifs.push(Motif::Pin {
+                    attacker: piece.position,
+                    pinned: front.position,
+                    behind: behind.position,
+                    absolute: behind.piece_type == PieceType::King,
+                });
+            }
+        }
+    }
+    motifs
+}
+
+/// The king of the color is stuck on its first rank, by its own pieces or
+/// attacked squares, while the other side has a rook or queen
+pub fn back_rank_weakness(chess: &Chess, color: Color) -> Option<Motif> {
+    back_rank_on_board(chess.board(), color)
+}
+
+fn back_rank_on_board(board: &Board, color: Color) -> Option<Motif> {
+    let king = find_king(board, color)?;
+    let (back_rank, forward) = match color {
+        Color::White => (0, 1),
+        Color::Black => (7, 6),
+    };
+    let has_heavy_piece = board
+        .iter()
+        .flatten()
+        .any(|p| p.color != color && matches!(p.piece_type, PieceType::Rook | PieceType::Queen));
+    if king.y != back_rank || !has_heavy_piece {
+        return None;
+    }
+
+    let stuck = (king.x.saturating_sub(1)..=(king.x + 1).min(7)).all(|x| {
+        let square = Position { x, y: forward };
+        board[square.to_index()]
+            .as_ref()
+            .is_some_and(|p| p.color == color)
+            || !attackers(board, square, !color).is_empty()
+    });
+    stuck.then_some(Motif::BackRankWeakness { king })
+}
+
+/// The tactics on the board for both colors: pins, skewers and back rank weaknesses
+pub fn position_motifs(chess: &Chess) -> Vec<Motif> {
+    let mut motifs = Vec::new();
+    for color in [Color::White, Color::Black] {
+        motifs.extend(line_motifs(chess.board(), color));
+        motifs.extend(back_rank_on_board(chess.board(), color));
+    }
+    motifs
+}
+
+/// The tactics the move creates for the side playing it: a fork by the moved piece,
+/// discovered attacks and checks, double check, and new pins, skewers and back rank
+/// weaknesses of the other side
+pub fn move_motifs(chess: &Chess, mov: &Move) -> Vec<Motif> {
+    let color = chess.side_to_move();
+    let mut after = chess.clone();
+    after.play(mov);
+    let board = after.board();
+    let mut motifs = Vec::new();
+
+    if let Some(piece) = after.piece_at(mov.to).filter(|p| p.color == color) {
+        let targets: Vec<Position> = board
+            .iter()
+            .flatten()
+            .filter(|p| p.color != color && attackers(board, p.position, color).contains(&mov.to))
+            .filter(|p| threatens(board, piece, p))
+            .map(|p| p.position)
+            .collect();
+        if targets.len() >= 2 {
+            motifs.push(Motif::Fork {
+                attacker: mov.to,
+                targets,
+            });
+        }
+    }
+
+    if let Some(king) = find_king(board, !color) {
+        let checkers = attackers(board, king, color);
+        if checkers.len() >= 2 {
+            motifs.push(Motif::DoubleCheck {
+                king,
+                checkers: checkers.clone(),
+            });
+        }
+        for &checker in checkers.iter() {
+            if checker != mov.to && is_between(mov.from, checker, king) {
+                motifs.push(Motif::DiscoveredCheck {
+                    attacker: checker,
+                    king,
+                });
+            }
+        }
+    }
+
+    for target in board
+        .iter()
+        .flatten()
+        .filter(|p| p.color != color && p.piece_type != PieceType::King)
+    {
+        for attacker in attackers(board, target.position, color) {
+            let piece = board[attacker.to_index()].as_ref().unwrap();
+            if attacker != mov.to
+                && is_between(mov.from, attacker, target.position)
+                && threatens(board, piece, target)
+            {
+                motifs.push(Motif::DiscoveredAttack {
+                    attacker,
+                    target: target.position,
+                });
+            }
+        }
+    }
+
+    let before = line_motifs(chess.board(), color);
+    motifs.extend(
+        line_motifs(board, color)
+            .into_iter()
+            .filter(|m| !before.contains(m)),
+    );
+    if back_rank_on_board(chess.board(), !color).is_none() {
+        motifs.extend(back_rank_on_board(board, !color));
+    }
+
+    motifs
+}
@@ -0,0 +1,156 @@
+use crate::eval::piece_value;
+use crate::moves::attackers;
+use crate::{Board, Chess, Color, Move, PieceType, Position, Status};
+
+/// The pieces of both colors attacking every square of a board
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackMap {
+    white: Vec<Vec<Position>>,
+    black: Vec<Vec<Position>>,
+}
+
+impl AttackMap {
+    pub fn new(board: &Board) -> Self {
+        let squares = |color| {
+            (0..64)
+                .map(|i| attackers(board, Position::from_index(i), color))
+                .collect()
+        };
+        AttackMap {
+            white: squares(Color::White),
+            black: squares(Color::Black),
+        }
+    }
+
+    /// The squares of the pieces of the color that attack the square
+    pub fn attackers(&self, square: Position, color: Color) -> &[Position] {
+        match color {
+            Color::White => &self.white[square.to_index()],
+            Color::Black => &self.black[square.to_index()],
+        }
+    }
+
+    pub fn is_attacked(&self, square: Position, by: Color) -> bool {
+        !self.attackers(square, by).is_empty()
+    }
+}
+
+/// A piece in danger and the pieces threatening it
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttackedPiece {
+    pub square: Position,
+    pub piece_type: PieceType,
+    pub color: Color,
+    pub attackers: Vec<Position>,
+}
+
+// Every piece of the color except the king, with its attackers kept by `keep`
+fn attacked_pieces(
+    board: &Board,
+    color: Color,
+    mut keep: impl FnMut(&AttackMap, Position, PieceType) -> Vec<Position>,
+) -> Vec<AttackedPiece> {
+    let map = AttackMap::new(board);
+    board
+        .iter()
+        .flatten()
+        .filter(|p| p.color == color && p.piece_type != PieceType::King)
+        .filter_map(|p| {
+            let attackers = keep(&map, p.position, p.piece_type);
+            (!attackers.is_empty()).then_some(AttackedPiece {
+                square: p.position,
+                piece_type: p.piece_type,
+                color,
+                attackers,
+            })
+        })
+        .collect()
+}
+
+fn hanging_on_board(board: &Board, color: Color) -> Vec<AttackedPiece> {
+    attacked_pieces(board, color, |map, square, _| {
+        if map.is_attacked(square, color) {
+            Vec::new()
+        } else {
+            map.attackers(square, !color).to_vec()
+        }
+    })
+}
+
+fn attacked_by_lesser_on_board(board: &Board, color: Color) -> Vec<AttackedPiece> {
+    attacked_pieces(board, color, |map, square, piece_type| {
+        map.attackers(square, !color)
+            .iter()
+            .filter(|a| {
+                board[a.to_index()].as_ref().is_some_and(|a| {
+                    a.piece_type != PieceType::King
+                        && piece_value(a.piece_type) < piece_value(piece_type)
+                })
+            })
+            .copied()
+            .collect()
+    })
+}
+
+/// Pieces of the color that are attacked and not defended at all
+pub fn hanging_pieces(chess: &Chess, color: Color) -> Vec<AttackedPiece> {
+    hanging_on_board(chess.board(), color)
+}
+
+/// Pieces of the color attacked by less valuable pieces, which win material even
+/// if the piece is defended. Only the less valuable attackers are listed.
+pub fn attacked_by_lesser(chess: &Chess, color: Color) -> Vec<AttackedPiece> {
+    attacked_by_lesser_on_board(chess.board(), color)
+}
+
+/// Pieces of the side to move that are hanging or attacked by less valuable pieces
+/// after the move, but were not before it. The moved piece counts as new on its square.
+pub fn hanging_after(chess: &Chess, mov: &Move) -> Vec<AttackedPiece> {
+    let color = chess.side_to_move();
+    let in_danger = |board: &Board| {
+        let mut pieces = hanging_on_board(board, color);
+        for attacked in attacked_by_lesser_on_board(board, color) {
+            if !pieces.iter().any(|p| p.square == attacked.square) {
+                pieces.push(attacked);
+            }
+        }
+        pieces
+    };
+
+    let before: Vec<Position> = in_danger(chess.board()).iter().map(|p| p.square).collect();
+    let mut after = chess.clone();
+    after.play(mov);
+    in_danger(after.board())
+        .into_iter()
+        .filter(|p| !before.contains(&p.square))
+        .collect()
+}
+
+/// The moves the other side would mate with if the side to move passed.
+/// Empty when the side to move is in check, since passing is then not possible.
+pub fn mate_threats(chess: &Chess) -> Vec<Move> {
+    if chess.is_check().is_some()
+        || matches!(chess.status(), Status::Checkmate(_) | Status::Draw(_))
+    {
+        return Vec::new();
+    }
+
+    let passed = Chess::from_parts(
+        chess.board().clone(),
+        !chess.side_to_move(),
+        chess.castling_rights(),
+        None,
+        chess.halfmove_clock(),
+        chess.fullmove_number(),
+    );
+    passed
+        .all_valid_moves()
+        .into_iter()
+        .filter(|mov| {
+            let mut child = passed.clone();
+            child.play(mov);
+            matches!(child.status(), Status::Checkmate(_))
+        })
+        .collect()
+}
@@ -0,0 +1,396 @@
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::search::{SearchLimits, SearchResult, Searcher, MAX_DEPTH};
+use crate::syzygy::Tablebase;
+use crate::{CastlingType, Chess, Color, Move, MoveType, Position, ValidationResult};
+
+const DEFAULT_HASH_MB: usize = 16;
+// Roughly how much memory an entry in the transposition table takes
+const TT_ENTRY_BYTES: usize = 128;
+
+/// The parameters of a `go` command, times in milliseconds
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    /// The `go` command to send to an engine with these parameters
+    pub fn to_command(&self) -> String {
+        let mut command = "go".to_string();
+        let values = [
+            ("depth", self.depth.map(|d| d as u64)),
+            ("nodes", self.nodes),
+            ("movetime", self.movetime),
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+            ("movestogo", self.movestogo),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value {
+                command.push_str(&format!(" {} {}", name, value));
+            }
+        }
+        if self.infinite {
+            command.push_str(" infinite");
+        }
+        command
+    }
+
+    /// The search limits for the side to move, sharing the clock time out over the coming moves
+    pub fn limits(&self, side_to_move: Color) -> SearchLimits {
+        let (time_left, increment) = match side_to_move {
+            Color::White => (self.wtime, self.winc),
+            Color::Black => (self.btime, self.binc),
+        };
+
+        let time = if self.infinite {
+            None
+        } else if let Some(movetime) = self.movetime {
+            Some(movetime)
+        } else {
+            time_left.map(|left| {
+                let moves_to_go = self.movestogo.unwrap_or(30).max(1);
+                let time = left / moves_to_go + increment.unwrap_or(0) * 3 / 4;
+                // Keep a margin so the clock never runs out
+                time.min(left / 2).max(1)
+            })
+        };
+
+        SearchLimits {
+            depth: self.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH),
+            nodes: if self.infinite { None } else { self.nodes },
+            time: time.map(Duration::from_millis),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UciCommand {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    UciNewGame,
+    /// `fen` is `None` for the start position, the moves are in UCI notation
+    Position {
+        fen: Option<String>,
+        moves: Vec<String>,
+    },
+    Go(GoParams),
+    Stop,
+    PonderHit,
+    Quit,
+    Unknown(String),
+}
+
+/// Parses a line sent to the engine
+pub fn parse_command(line: &str) -> UciCommand {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let unknown = || UciCommand::Unknown(line.trim().to_string());
+
+    match words.first().copied() {
+        Some("uci") => UciCommand::Uci,
+        Some("debug") => UciCommand::Debug(words.get(1) == Some(&"on")),
+        Some("isready") => UciCommand::IsReady,
+        Some("ucinewgame") => UciCommand::UciNewGame,
+        Some("stop") => UciCommand::Stop,
+        Some("ponderhit") => UciCommand::PonderHit,
+        Some("quit") => UciCommand::Quit,
+        Some("setoption") => {
+            // Names and values can contain spaces
+            let name_start = match words.iter().position(|w| *w == "name") {
+                Some(i) => i + 1,
+                None => return unknown(),
+            };
+            let value_start = words.iter().position(|w| *w == "value");
+            let name = words[name_start..value_start.unwrap_or(words.len())].join(" ");
+            let value = value_start.map(|i| words[i + 1..].join(" "));
+            UciCommand::SetOption { name, value }
+        }
+        Some("position") => {
+            let moves_start = words.iter().position(|w| *w == "moves");
+            let moves = moves_start.map_or(Vec::new(), |i| {
+                words[i + 1..].iter().map(|w| w.to_string()).collect()
+            });
+            let setup = &words[1..moves_start.unwrap_or(words.len())];
+            match setup.first() {
+                Some(&"startpos") => UciCommand::Position { fen: None, moves },
+                Some(&"fen") => UciCommand::Position {
+                    fen: Some(setup[1..].join(" ")),
+                    moves,
+                },
+                _ => unknown(),
+            }
+        }
+        Some("go") => {
+            let mut params = GoParams::default();
+            let mut i = 1;
+            while i < words.len() {
+                let value = words.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+                match words[i] {
+                    "infinite" => params.infinite = true,
+                    "depth" => params.depth = value.map(|v| v.min(u8::MAX as u64) as u8),
+                    "nodes" => params.nodes = value,
+                    "movetime" => params.movetime = value,
+                    "wtime" => params.wtime = value,
+                    "btime" => params.btime = value,
+                    "winc" => params.winc = value,
+                    "binc" => params.binc = value,
+                    "movestogo" => params.movestogo = value,
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                }
+                i += if words[i] == "infinite" { 1 } else { 2 };
+            }
+            UciCommand::Go(params)
+        }
+        _ => unknown(),
+    }
+}
+
+/// Writes the move like UCI does, where castling is the king moving two squares, e.g. `e1g1`
+pub fn move_to_uci(chess: &Chess, mov: &Move) -> String {
+    match chess.get_move_type(mov.from, mov.to) {
+        MoveType::Castling(castling_type) => {
+            let king_to = match castling_type {
+                CastlingType::KingSide(_) => 6,
+                CastlingType::QueenSide(_) => 2,
+            };
+            format!(
+                "{}{}",
+                mov.from,
+                Position {
+                    x: king_to,
+                    y: mov.from.y
+                }
+            )
+        }
+        _ => mov.to_string(),
+    }
+}
+
+/// Finds the valid move written in UCI notation. Castling can be written both
+/// as the king moving two squares and as the king taking its own rook.
+pub fn parse_uci_move(chess: &Chess, s: &str) -> Option<Move> {
+    chess
+        .all_valid_moves()
+        .into_iter()
+        .find(|m| move_to_uci(chess, m) == s || m.to_string() == s)
+}
+
+/// Sets up the position of a `position` command
+pub fn position_from_command(fen: Option<&str>, moves: &[String]) -> Option<Chess> {
+    let mut chess = match fen {
+        Some(fen) => Chess::from_fen(fen, false).ok()?,
+        None => Chess::new(),
+    };
+    for s in moves.iter() {
+        let mov = parse_uci_move(&chess, s)?;
+        if !matches!(chess.play(&mov), ValidationResult::Valid(_)) {
+            return None;
+        }
+    }
+    Some(chess)
+}
+
+/// The `info` line for a finished depth of the search
+pub fn format_info(chess: &Chess, result: &SearchResult) -> String {
+    let score = match result.mate_in() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let ms = result.stats.time.as_millis() as u64;
+    let nps = result.stats.nodes * 1000 / ms.max(1);
+
+    let mut pv = Vec::new();
+    let mut position = chess.clone();
+    for mov in result.pv.iter() {
+        pv.push(move_to_uci(&position, mov));
+        position.play(mov);
+    }
+
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        score,
+        result.stats.nodes,
+        nps,
+        ms,
+        pv.join(" ")
+    )
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    // Nothing to do if the GUI has gone away
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+struct RunningSearch {
+    handle: JoinHandle<Searcher>,
+    infinite: bool,
+}
+
+// The searcher is moved into the search thread and given back when it is done
+fn wait_for_search(running: &mut Option<RunningSearch>, searcher: &mut Option<Searcher>) {
+    if let Some(search) = running.take() {
+        *searcher = Some(search.handle.join().unwrap());
+    }
+}
+
+// A search with `go infinite` only ends on stop, so anything else waiting for it stops it first
+fn stop_search(
+    stop: &AtomicBool,
+    running: &mut Option<RunningSearch>,
+    searcher: &mut Option<Searcher>,
+) {
+    stop.store(true, Ordering::Relaxed);
+    wait_for_search(running, searcher);
+}
+
+/// Runs the engine, reading commands until `quit` or the end of the input
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let output = Arc::new(Mutex::new(output));
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut chess = Chess::new();
+    let mut searcher = Some(Searcher::new());
+    let mut running: Option<RunningSearch> = None;
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        match parse_command(&line) {
+            UciCommand::Uci => {
+                send(
+                    &output,
+                    &format!("id name chess {}", env!("CARGO_PKG_VERSION")),
+                );
+                send(&output, "id author liamt");
+                send(
+                    &output,
+                    &format!(
+                        "option name Hash type spin default {} min 1 max 1024",
+                        DEFAULT_HASH_MB
+                    ),
+                );
+                send(&output, "option name Clear Hash type button");
+                send(
+                    &output,
+                    "option name SyzygyPath type string default <empty>",
+                );
+                send(&output, "uciok");
+            }
+            UciCommand::IsReady => send(&output, "readyok"),
+            UciCommand::UciNewGame => {
+                stop_search(&stop, &mut running, &mut searcher);
+                searcher.as_mut().unwrap().clear();
+                chess = Chess::new();
+            }
+            UciCommand::SetOption { name, value } => {
+                stop_search(&stop, &mut running, &mut searcher);
+                let searcher = searcher.as_mut().unwrap();
+                match name.to_lowercase().as_str() {
+                    "hash" => {
+                        if let Some(mb) = value.and_then(|v| v.parse::<usize>().ok()) {
+                            searcher.tt_size = mb.max(1) * 1024 * 1024 / TT_ENTRY_BYTES;
+                            searcher.clear();
+                        }
+                    }
+                    "clear hash" => searcher.clear(),
+                    "syzygypath" => {
+                        // An empty value, or the default shown to the GUI, turns them off
+                        let value = value.unwrap_or_default();
+                        let value = value.trim();
+                        if value.is_empty() || value == "<empty>" {
+                            searcher.tablebase = None;
+                        } else {
+                            // Several directories are separated like in PATH
+                            let mut tablebase = Tablebase::new();
+                            for dir in std::env::split_paths(value) {
+                                if tablebase.add_directory(&dir).is_err() {
+                                    send(&output, "info string could not read tablebase directory");
+                                }
+                            }
+                            searcher.tablebase =
+                                Some(Arc::new(tablebase)).filter(|tb| tb.max_pieces() > 0);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            UciCommand::Position { fen, moves } => {
+                stop_search(&stop, &mut running, &mut searcher);
+                match position_from_command(fen.as_deref(), &moves) {
+                    Some(position) => chess = position,
+                    None => send(&output, "info string invalid position"),
+                }
+            }
+            UciCommand::Go(params) => {
+                stop_search(&stop, &mut running, &mut searcher);
+                stop.store(false, Ordering::Relaxed);
+
+                let mut thread_searcher = searcher.take().unwrap();
+                thread_searcher.set_stop_signal(stop.clone());
+                let limits = params.limits(chess.side_to_move());
+                let position = chess.clone();
+                let output = output.clone();
+                let stop = stop.clone();
+                let infinite = params.infinite;
+
+                let handle = std::thread::spawn(move || {
+                    let result = thread_searcher.search_with_info(&position, &limits, |r| {
+                        send(&output, &format_info(&position, r));
+                    });
+                    // With infinite the best move is only sent after stop
+                    while infinite && !stop.load(Ordering::Relaxed) {
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                    let best = result
+                        .best_move
+                        .map_or("0000".to_string(), |m| move_to_uci(&position, &m));
+                    send(&output, &format!("bestmove {}", best));
+                    thread_searcher
+                });
+                running = Some(RunningSearch { handle, infinite });
+            }
+            UciCommand::Stop => {
+                stop_search(&stop, &mut running, &mut searcher);
+            }
+            UciCommand::Quit => {
+                stop_search(&stop, &mut running, &mut searcher);
+                return;
+            }
+            UciCommand::Debug(_) | UciCommand::PonderHit | UciCommand::Unknown(_) => {}
+        }
+    }
+
+    // The input ended, let a limited search finish but don't wait forever for `stop`
+    if running.as_ref().is_some_and(|r| r.infinite) {
+        stop.store(true, Ordering::Relaxed);
+    }
+    wait_for_search(&mut running, &mut searcher);
+}
@@ -0,0 +1,123 @@
+use crate::moves::king_in_check;
+use crate::{CastlingRights, CastlingType, Chess, Color, PieceType, Position};
+
+/// Something that makes a position impossible to reach in a real game
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PositionIssue {
+    MissingKing(Color),
+    TooManyKings(Color),
+    PawnOnBackRank(Position),
+    TooManyPawns(Color),
+    TooManyPieces(Color),
+    // More queens, rooks, bishops or knights than the missing pawns could have promoted to
+    TooManyPromotedPieces(Color),
+    OpponentInCheck(Color),
+    InvalidEnPassant(Position),
+    InvalidCastlingRights(CastlingType),
+}
+
+impl Chess {
+    /// Lists every reason the position could not have occurred in a game.
+    /// An empty list means the position is legal.
+    pub fn validate_position(&self) -> Vec<PositionIssue> {
+        let mut issues = Vec::new();
+
+        for color in [Color::White, Color::Black] {
+            let count = |piece_type: PieceType| {
+                self.board
+                    .iter()
+                    .flatten()
+                    .filter(|p| p.color == color && p.piece_type == piece_type)
+                    .count()
+            };
+
+            match count(PieceType::King) {
+                0 => issues.push(PositionIssue::MissingKing(color)),
+                1 => {}
+                _ => issues.push(PositionIssue::TooManyKings(color)),
+            }
+
+            let pawns = count(PieceType::Pawn);
+            if pawns > 8 {
+                issues.push(PositionIssue::TooManyPawns(color));
+            }
+
+            let total = self
+                .board
+                .iter()
+                .flatten()
+                .filter(|p| p.color == color)
+                .count();
+            if total > 16 {
+                issues.push(PositionIssue::TooManyPieces(color));
+            }
+
+            let promoted = count(PieceType::Queen).saturating_sub(1)
+                + count(PieceType::Rook).saturating_sub(2)
+                + count(PieceType::Bishop).saturating_sub(2)
+                + count(PieceType::Knight).saturating_sub(2);
+            if promoted > 8_usize.saturating_sub(pawns) {
+                issues.push(PositionIssue::TooManyPromotedPieces(color));
+            }
+        }
+
+        for piece in self.board.iter().flatten() {
+            if piece.piece_type == PieceType::Pawn
+                && (piece.position.y == 0 || piece.position.y == 7)
+            {
+                issues.push(PositionIssue::PawnOnBackRank(piece.position));
+            }
+        }
+
+        // Waiting for a promotion the turn hasn't passed yet, so the mover is still "to move"
+        if self.awaiting_promotion_piece.is_none() && king_in_check(&self.board, !self.turn) {
+            issues.push(PositionIssue::OpponentInCheck(!self.turn));
+        }
+
+        if let Some(target) = self.en_passant {
+            if !self.en_passant_possible(target) {
+                issues.push(PositionIssue::InvalidEnPassant(target));
+            }
+        }
+
+        for castling_type in CastlingRights::ALL {
+            if !self.castling_rights.has(castling_type) {
+                continue;
+            }
+            let (king, rook) = castling_type.king_and_rook_squares();
+            let is_piece = |pos: Position, piece_type: PieceType| {
+                self.board[pos.to_index()]
+                    .as_ref()
+                    .is_some_and(|p| p.piece_type == piece_type && p.color == castling_type.color())
+            };
+            if !is_piece(king, PieceType::King) || !is_piece(rook, PieceType::Rook) {
+                issues.push(PositionIssue::InvalidCastlingRights(castling_type));
+            }
+        }
+
+        issues
+    }
+
+    // The en passant square has to be right behind a pawn that just moved two steps
+    fn en_passant_possible(&self, target: Position) -> bool {
+        let mover = !self.turn;
+        let (target_row, pawn_row, start_row) = match mover {
+            Color::White => (2, 3, 1),
+            Color::Black => (5, 4, 6),
+        };
+
+        if target.y != target_row {
+            return false;
+        }
+
+        let pawn = &self.board[pawn_row * 8 + target.x];
+        let pawn_ok = pawn
+            .as_ref()
+            .is_some_and(|p| p.piece_type == PieceType::Pawn && p.color == mover);
+
+        pawn_ok
+            && self.board[target.to_index()].is_none()
+            && self.board[start_row * 8 + target.x].is_none()
+    }
+}
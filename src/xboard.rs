@@ -0,0 +1,332 @@
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::search::{SearchResult, Searcher, MAX_DEPTH};
+use crate::uci::{move_to_uci, parse_uci_move, GoParams};
+use crate::{Chess, Color, DrawType, Move, Status, ValidationResult};
+
+// The time control set with `level`, `st` and `sd`
+#[derive(Clone, Debug, Default)]
+struct TimeControl {
+    moves_per_session: u64,
+    increment_ms: u64,
+    // Fixed time for every move, from `st`
+    move_time_ms: Option<u64>,
+    depth: Option<u8>,
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    // Nothing to do if the GUI has gone away
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+// The result to report if the last move ended the game
+fn result_line(chess: &Chess) -> Option<&'static str> {
+    match chess.status() {
+        Status::Checkmate(Color::Black) => Some("1-0 {White mates}"),
+        Status::Checkmate(Color::White) => Some("0-1 {Black mates}"),
+        Status::Draw(DrawType::Stalemate) => Some("1/2-1/2 {Stalemate}"),
+        Status::Draw(DrawType::ThreefoldRepetition) => Some("1/2-1/2 {Draw by repetition}"),
+        Status::Draw(DrawType::FiftyMoveRule) => Some("1/2-1/2 {Draw by fifty move rule}"),
+        _ => None,
+    }
+}
+
+struct XBoardEngine<W: Write> {
+    output: Arc<Mutex<W>>,
+    // Moved into the search thread while the engine thinks
+    searcher: Option<Searcher>,
+    // Every position of the game, so moves can be taken back
+    history: Vec<Chess>,
+    engine_color: Option<Color>,
+    time_control: TimeControl,
+    // Centiseconds on the clocks, as sent with `time` and `otim`
+    engine_time: Option<u64>,
+    opponent_time: Option<u64>,
+    post: bool,
+    // Ends the search early, the move found so far is still played
+    stop: Arc<AtomicBool>,
+    // The game changed while thinking, so the move of the search is not played
+    abort: Arc<AtomicBool>,
+    // Gives back the searcher, and the position after the move if it was played
+    thinking: Option<JoinHandle<(Searcher, Option<Chess>)>>,
+}
+
+impl<W: Write + Send + 'static> XBoardEngine<W> {
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    fn position(&self) -> &Chess {
+        self.history.last().unwrap()
+    }
+
+    fn new_game(&mut self, start: Chess) {
+        self.history = vec![start];
+        self.searcher.as_mut().unwrap().clear();
+    }
+
+    fn is_game_over(&self) -> bool {
+        matches!(
+            self.position().status(),
+            Status::Checkmate(_) | Status::Draw(_)
+        )
+    }
+
+    fn send_result(&self) {
+        if let Some(result) = result_line(self.position()) {
+            self.send(result);
+        }
+    }
+
+    fn play(&mut self, mov: &Move) -> bool {
+        let mut chess = self.position().clone();
+        if !matches!(chess.play(mov), ValidationResult::Valid(_)) {
+            return false;
+        }
+        self.history.push(chess);
+        true
+    }
+
+    fn go_params(&self) -> GoParams {
+        let color = self.position().side_to_move();
+        let mut params = GoParams {
+            depth: self.time_control.depth,
+            movetime: self.time_control.move_time_ms,
+            ..Default::default()
+        };
+
+        if let Some(time) = self.engine_time.filter(|_| params.movetime.is_none()) {
+            let ms = Some(time * 10);
+            let opponent_ms = self.opponent_time.map(|time| time * 10);
+            let inc = Some(self.time_control.increment_ms);
+            if color == Color::White {
+                (params.wtime, params.btime) = (ms, opponent_ms);
+            } else {
+                (params.wtime, params.btime) = (opponent_ms, ms);
+            }
+            (params.winc, params.binc) = (inc, inc);
+            if self.time_control.moves_per_session > 0 {
+                let played = (self.position().fullmove_number() as u64 - 1)
+                    % self.time_control.moves_per_session;
+                params.movestogo = Some(self.time_control.moves_per_session - played);
+            }
+        }
+
+        if params.depth.is_none()
+            && params.movetime.is_none()
+            && params.wtime.is_none()
+            && params.btime.is_none()
+        {
+            // Without a time control, think for a few seconds
+            params.movetime = Some(5000);
+        }
+        params
+    }
+
+    // Starts searching on another thread, which sends the move when it is done
+    fn think(&mut self) {
+        if self.is_game_over() {
+            return;
+        }
+
+        let chess = self.position().clone();
+        let limits = self.go_params().limits(chess.side_to_move());
+        let post = self.post;
+        let mut searcher = self.searcher.take().unwrap();
+        let output = self.output.clone();
+        let abort = self.abort.clone();
+        self.stop.store(false, Ordering::Relaxed);
+        abort.store(false, Ordering::Relaxed);
+
+        self.thinking = Some(std::thread::spawn(move || {
+            let result = searcher.search_with_info(&chess, &limits, |r| {
+                if post {
+                    send(&output, &thinking_line(&chess, r));
+                }
+            });
+
+            let mut played = None;
+            if let Some(mov) = result.best_move.filter(|_| !abort.load(Ordering::Relaxed)) {
+                let mut after = chess.clone();
+                after.play(&mov);
+                send(&output, &format!("move {}", move_to_uci(&chess, &mov)));
+                if let Some(result) = result_line(&after) {
+                    send(&output, result);
+                }
+                played = Some(after);
+            }
+            (searcher, played)
+        }));
+    }
+
+    // Waits for the search to end and adds its move to the game
+    fn wait_for_search(&mut self) {
+        if let Some(handle) = self.thinking.take() {
+            let (searcher, played) = handle.join().unwrap();
+            self.searcher = Some(searcher);
+            self.history.extend(played);
+        }
+    }
+
+    fn user_move(&mut self, text: &str) {
+        let chess = self.position().clone();
+        let mov = parse_uci_move(&chess, text).or_else(|| chess.parse_san(text));
+        match mov {
+            Some(mov) if self.play(&mov) => {
+                self.send_result();
+                if self.engine_color == Some(self.position().side_to_move()) {
+                    self.think();
+                }
+            }
+            _ => self.send(&format!("Illegal move: {}", text)),
+        }
+    }
+
+    // Returns false when the engine should exit
+    fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args = words.get(1..).unwrap_or(&[]);
+        let arg = |i: usize| args.get(i).and_then(|v| v.parse::<u64>().ok());
+
+        // Only `?` is handled while thinking, other commands wait for the move
+        // or give up the search when they change the game
+        match words.first().copied() {
+            Some("?") => {
+                self.stop.store(true, Ordering::Relaxed);
+                return true;
+            }
+            Some("quit") => self.stop.store(true, Ordering::Relaxed),
+            Some("new") | Some("force") | Some("setboard") | Some("undo") | Some("remove")
+            | Some("result") => {
+                self.abort.store(true, Ordering::Relaxed);
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        self.wait_for_search();
+
+        match words.first().copied() {
+            None => {}
+            Some("xboard") | Some("accepted") | Some("rejected") | Some("random")
+            | Some("hard") | Some("easy") | Some("computer") => {}
+            Some("protover") => {
+                let features = format!(
+                    "feature myname=\"chess {}\" setboard=1 usermove=1 ping=1 playother=1 san=0 colors=0 sigint=0 sigterm=0 analyze=0 done=1",
+                    env!("CARGO_PKG_VERSION")
+                );
+                self.send(&features);
+            }
+            Some("new") => {
+                self.new_game(Chess::new());
+                self.engine_color = Some(Color::Black);
+                self.time_control.depth = None;
+                self.time_control.move_time_ms = None;
+            }
+            Some("force") => self.engine_color = None,
+            Some("go") => {
+                self.engine_color = Some(self.position().side_to_move());
+                self.think();
+            }
+            Some("playother") => self.engine_color = Some(!self.position().side_to_move()),
+            Some("usermove") => {
+                if let Some(mov) = args.first() {
+                    self.user_move(mov);
+                }
+            }
+            Some("setboard") => match Chess::from_fen(&args.join(" "), false) {
+                Ok(chess) => self.new_game(chess),
+                Err(_) => self.send("tellusererror Illegal position"),
+            },
+            Some("undo") if self.history.len() > 1 => {
+                self.history.pop();
+            }
+            Some("remove") if self.history.len() > 2 => {
+                self.history.truncate(self.history.len() - 2);
+            }
+            Some("undo") | Some("remove") => {}
+            Some("level") => {
+                self.time_control.moves_per_session = arg(0).unwrap_or(0);
+                self.time_control.increment_ms = args
+                    .get(2)
+                    .and_then(|v| v.parse::<f64>().ok())
+                    .map_or(0, |s| (s * 1000.0) as u64);
+                self.time_control.move_time_ms = None;
+            }
+            Some("st") => self.time_control.move_time_ms = arg(0).map(|s| s * 1000),
+            Some("sd") => self.time_control.depth = arg(0).map(|d| d.min(MAX_DEPTH as u64) as u8),
+            Some("time") => self.engine_time = arg(0),
+            Some("otim") => self.opponent_time = arg(0),
+            Some("result") => self.engine_color = None,
+            Some("ping") => self.send(&format!("pong {}", args.first().unwrap_or(&""))),
+            Some("post") => self.post = true,
+            Some("nopost") => self.post = false,
+            Some("quit") => return false,
+            // Moves can also be sent without `usermove`
+            Some(text) => {
+                if parse_uci_move(self.position(), text).is_some() {
+                    self.user_move(text);
+                } else {
+                    self.send(&format!("Error (unknown command): {}", text));
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// A thinking line, with the score in centipawns and the time in centiseconds
+fn thinking_line(chess: &Chess, result: &SearchResult) -> String {
+    let mut pv = Vec::new();
+    let mut position = chess.clone();
+    for mov in result.pv.iter() {
+        pv.push(move_to_uci(&position, mov));
+        position.play(mov);
+    }
+    format!(
+        "{} {} {} {} {}",
+        result.depth,
+        result.score,
+        result.stats.time.as_millis() / 10,
+        result.stats.nodes,
+        pv.join(" ")
+    )
+}
+
+/// Runs the engine with the Chess Engine Communication Protocol used by XBoard,
+/// reading commands until `quit` or the end of the input
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut searcher = Searcher::new();
+    searcher.set_stop_signal(stop.clone());
+    let mut engine = XBoardEngine {
+        output: Arc::new(Mutex::new(output)),
+        searcher: Some(searcher),
+        history: vec![Chess::new()],
+        engine_color: Some(Color::Black),
+        time_control: TimeControl::default(),
+        engine_time: None,
+        opponent_time: None,
+        post: false,
+        stop,
+        abort: Arc::new(AtomicBool::new(false)),
+        thinking: None,
+    };
+
+    for line in input.lines() {
+        match line {
+            Ok(line) => {
+                if !engine.handle(&line) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    engine.wait_for_search();
+}
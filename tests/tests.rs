@@ -5,14 +5,14 @@ mod tests {
     #[test]
     fn check_turn_on_default_board() {
         let chess = Chess::new();
-        assert_eq!(chess.turn, Color::White);
+        assert_eq!(chess.side_to_move(), Color::White);
     }
 
     #[test]
     fn check_2_kings_on_default_board() {
         let chess = Chess::new();
         let kings = chess
-            .board
+            .board()
             .iter()
             .filter(|&p| match p {
                 Some(piece) => piece.piece_type == PieceType::King,
@@ -24,26 +24,26 @@ mod tests {
 
     #[test]
     fn check_invalid_fen() {
-        assert!(Chess::from_fen("k7/8/8/8/8/8/8/1R6").is_err()); // missing turn
-        assert!(Chess::from_fen("k7/8/8/8/8/8/8/1R6 a").is_err()); // invalid turn
-        assert!(Chess::from_fen("k7/8/8/8/8/8/8/8/8 w").is_err()); // too many rows
-        assert!(Chess::from_fen("k7/8/8/8/8/8/8/9 w").is_err()); // too many columns
+        assert!(Chess::from_fen("k7/8/8/8/8/8/8/1R6", false).is_err()); // missing turn
+        assert!(Chess::from_fen("k7/8/8/8/8/8/8/1R6 a", false).is_err()); // invalid turn
+        assert!(Chess::from_fen("k7/8/8/8/8/8/8/8/8 w", false).is_err()); // too many rows
+        assert!(Chess::from_fen("k7/8/8/8/8/8/8/9 w", false).is_err()); // too many columns
     }
 
     #[test]
     fn check_check() {
-        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/1R6 w").unwrap();
+        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/1R6 w", false).unwrap();
         chess.move_piece(Position::from_str("b1"), Position::from_str("a1"));
-        assert_eq!(chess.status, Status::Check(Color::Black));
+        assert_eq!(chess.status(), Status::Check(Color::Black));
 
-        let mut chess = Chess::from_fen("7k/8/7N/8/8/8/8/8 w").unwrap();
+        let mut chess = Chess::from_fen("7k/8/7N/8/8/8/8/8 w", false).unwrap();
         chess.move_piece(Position::from_str("h6"), Position::from_str("f7"));
-        assert_eq!(chess.status, Status::Check(Color::Black));
+        assert_eq!(chess.status(), Status::Check(Color::Black));
     }
 
     #[test]
     fn check_valid_moves() {
-        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/1Q6 w").unwrap();
+        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/1Q6 w", false).unwrap();
 
         let res = chess.move_piece(Position::from_str("a8"), Position::from_str("a7"));
         assert!(matches!(res, ValidationResult::InvalidTurn));
@@ -57,15 +57,15 @@ mod tests {
 
     #[test]
     fn check_stalemate() {
-        let mut chess = Chess::from_fen("k7/8/2Q5/8/8/8/8/K7 w").unwrap();
+        let mut chess = Chess::from_fen("k7/8/2Q5/8/8/8/8/K7 w", false).unwrap();
         chess.move_piece(Position::from_str("c6"), Position::from_str("b6"));
-        assert_eq!(chess.status, Status::Draw(DrawType::Stalemate));
+        assert_eq!(chess.status(), Status::Draw(DrawType::Stalemate));
 
-        let mut chess = Chess::from_fen("1B6/8/8/3k4/8/B7/8/2R1R3 w").unwrap();
+        let mut chess = Chess::from_fen("1B6/8/8/3k4/8/B7/8/2R1R3 w", false).unwrap();
         chess.move_piece(Position::from_str("a3"), Position::from_str("b2"));
-        assert_eq!(chess.status, Status::Draw(DrawType::Stalemate));
+        assert_eq!(chess.status(), Status::Draw(DrawType::Stalemate));
 
-        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/K1R3Q1 w").unwrap();
+        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/K1R3Q1 w", false).unwrap();
         let res = chess.move_piece(Position::from_str("c1"), Position::from_str("b1"));
         assert_eq!(
             res,
@@ -75,73 +75,79 @@ mod tests {
 
     #[test]
     fn check_checkmate() {
-        let mut chess = Chess::from_fen("k7/7R/2Q5/8/8/8/8/K7 w").unwrap();
+        let mut chess = Chess::from_fen("k7/7R/2Q5/8/8/8/8/K7 w", false).unwrap();
         chess.move_piece(Position::from_str("c6"), Position::from_str("b7"));
-        assert_eq!(chess.status, Status::Checkmate(Color::Black));
-        assert_eq!(chess.winner, Some(Color::White));
+        assert_eq!(chess.status(), Status::Checkmate(Color::Black));
+        assert_eq!(chess.winner(), Some(Color::White));
 
-        let mut chess = Chess::from_fen("k7/2QN3R/1P6/1N6/8/8/8/K7 w").unwrap();
+        let mut chess = Chess::from_fen("k7/2QN3R/1P6/1N6/8/8/8/K7 w", false).unwrap();
         chess.move_piece(Position::from_str("b6"), Position::from_str("b7"));
-        assert_eq!(chess.status, Status::Checkmate(Color::Black));
-        assert_eq!(chess.winner, Some(Color::White));
+        assert_eq!(chess.status(), Status::Checkmate(Color::Black));
+        assert_eq!(chess.winner(), Some(Color::White));
 
-        let chess = Chess::from_fen("k7/8/8/8/8/8/8/QR6 w").unwrap();
-        assert_eq!(chess.status, Status::Checkmate(Color::Black));
-        assert_eq!(chess.winner, Some(Color::White));
+        let chess = Chess::from_fen("k7/8/8/8/8/8/8/QR6 w", false).unwrap();
+        assert_eq!(chess.status(), Status::Checkmate(Color::Black));
+        assert_eq!(chess.winner(), Some(Color::White));
 
-        let mut chess = Chess::from_fen("k7/8/8/8/8/8/1R6/KR4Q1 w").unwrap();
+        let mut chess = Chess::from_fen("k7/8/8/8/8/8/1R6/KR4Q1 w", false).unwrap();
         let res = chess.move_piece(Position::from_str("b2"), Position::from_str("a2"));
         assert_eq!(
             res,
             ValidationResult::Valid(Status::Checkmate(Color::Black))
         );
-        assert_eq!(chess.winner, Some(Color::White));
+        assert_eq!(chess.winner(), Some(Color::White));
     }
 
     #[test]
     fn check_promotion() {
-        let mut chess = Chess::from_fen("7k/P7/8/8/8/8/8/P6K w").unwrap();
+        let mut chess = Chess::from_fen("7k/P7/8/8/8/8/8/P6K w", false).unwrap();
         chess.move_piece(Position::from_str("a7"), Position::from_str("a8"));
-        assert_eq!(chess.status, Status::AwaitingPromotion);
+        assert_eq!(chess.status(), Status::AwaitingPromotion);
         chess.promote_piece(PieceType::Queen);
-        assert_eq!(chess.status, Status::Check(Color::Black));
+        assert_eq!(chess.status(), Status::Check(Color::Black));
     }
 
     #[test]
     fn check_en_passant() {
-        let mut chess = Chess::from_fen("k7/2p5/8/3P4/8/8/8/K7 b").unwrap();
+        let mut chess = Chess::from_fen("k7/2p5/8/3P4/8/8/8/K7 b", false).unwrap();
         chess.move_piece(Position::from_str("c7"), Position::from_str("c5"));
 
         let res = chess.move_piece(Position::from_str("d5"), Position::from_str("c6"));
 
         assert!(matches!(res, ValidationResult::Valid(_)));
-        assert!(chess.board[4 * 8 + 2].is_none());
+        assert!(chess.board()[4 * 8 + 2].is_none());
     }
 
     #[test]
     fn check_castling() {
-        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R3K3 w").unwrap();
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R3K3 w", false).unwrap();
         let res = chess.move_piece(Position::from_str("e1"), Position::from_str("a1"));
         assert_eq!(res, ValidationResult::Valid(Status::Chilling));
-        assert_eq!(chess.board[2].as_ref().unwrap().piece_type, PieceType::King);
-        assert_eq!(chess.board[3].as_ref().unwrap().piece_type, PieceType::Rook);
+        assert_eq!(
+            chess.board()[2].as_ref().unwrap().piece_type,
+            PieceType::King
+        );
+        assert_eq!(
+            chess.board()[3].as_ref().unwrap().piece_type,
+            PieceType::Rook
+        );
 
-        let mut chess = Chess::from_fen("2q1k3/8/8/8/8/8/8/R3K3 w").unwrap();
+        let mut chess = Chess::from_fen("2q1k3/8/8/8/8/8/8/R3K3 w", false).unwrap();
         let res = chess.move_piece(Position::from_str("e1"), Position::from_str("a1"));
         assert_eq!(res, ValidationResult::InvalidMove);
 
-        let mut chess = Chess::from_fen("3qk3/8/8/8/8/8/8/R3K3 w").unwrap();
+        let mut chess = Chess::from_fen("3qk3/8/8/8/8/8/8/R3K3 w", false).unwrap();
         let res = chess.move_piece(Position::from_str("e1"), Position::from_str("a1"));
         assert_eq!(res, ValidationResult::InvalidMove);
 
-        let mut chess = Chess::from_fen("r3kq2/8/8/8/8/8/8/RQ2K3 b").unwrap();
+        let mut chess = Chess::from_fen("r3kq2/8/8/8/8/8/8/RQ2K3 b", false).unwrap();
         let res = chess.move_piece(Position::from_str("e8"), Position::from_str("a8"));
         assert_eq!(res, ValidationResult::Valid(Status::Chilling));
 
         let res = chess.move_piece(Position::from_str("e1"), Position::from_str("a1"));
         assert_eq!(res, ValidationResult::InvalidMove);
 
-        let mut chess = Chess::from_fen("q3k2r/8/8/8/8/8/5Q2/4K2R b").unwrap();
+        let mut chess = Chess::from_fen("q3k2r/8/8/8/8/8/5Q2/4K2R b", false).unwrap();
         let res = chess.move_piece(Position::from_str("e8"), Position::from_str("h8"));
         assert_eq!(res, ValidationResult::InvalidMove);
         let res = chess.move_piece(Position::from_str("e8"), Position::from_str("f8"));
@@ -152,7 +158,7 @@ mod tests {
         let res = chess.move_piece(Position::from_str("e1"), Position::from_str("h1"));
         assert_eq!(res, ValidationResult::Valid(Status::Chilling));
 
-        let mut chess = Chess::from_fen("q3k2r/8/8/8/8/8/5Q2/4K1R1 w").unwrap();
+        let mut chess = Chess::from_fen("q3k2r/8/8/8/8/8/5Q2/4K1R1 w", false).unwrap();
         let res = chess.move_piece(Position::from_str("g1"), Position::from_str("h1"));
         assert_eq!(res, ValidationResult::Valid(Status::Chilling));
         let res = chess.move_piece(Position::from_str("e8"), Position::from_str("d8"));
@@ -161,9 +167,50 @@ mod tests {
         assert_eq!(res, ValidationResult::InvalidMove);
     }
 
+    #[test]
+    fn check_castling_rights_and_en_passant_state() {
+        let mut chess = Chess::new();
+        chess.move_piece(Position::from_str("e2"), Position::from_str("e4"));
+        assert_eq!(chess.en_passant(), Some(Position::from_str("e3")));
+        assert_eq!(
+            chess.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        chess.move_piece(Position::from_str("g8"), Position::from_str("f6"));
+        assert_eq!(chess.en_passant(), None);
+        assert_eq!(chess.fullmove_number(), 2);
+        assert_eq!(chess.halfmove_clock(), 1);
+
+        // En passant is only allowed right after the two step move
+        let mut chess = Chess::from_fen("k7/2p5/8/3P4/8/8/8/K7 b", false).unwrap();
+        chess.move_piece(Position::from_str("c7"), Position::from_str("c5"));
+        chess.move_piece(Position::from_str("a1"), Position::from_str("a2"));
+        chess.move_piece(Position::from_str("a8"), Position::from_str("b8"));
+        let res = chess.move_piece(Position::from_str("d5"), Position::from_str("c6"));
+        assert_eq!(res, ValidationResult::InvalidMove);
+
+        // Moving a rook away and back loses the right to castle with it
+        let mut chess = Chess::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq", false).unwrap();
+        chess.move_piece(Position::from_str("h1"), Position::from_str("h2"));
+        chess.move_piece(Position::from_str("a8"), Position::from_str("a7"));
+        assert_eq!(chess.castling_rights().to_fen(), "Qk");
+        chess.move_piece(Position::from_str("h2"), Position::from_str("h1"));
+        chess.move_piece(Position::from_str("a7"), Position::from_str("a8"));
+        let res = chess.move_piece(Position::from_str("e1"), Position::from_str("h1"));
+        assert_eq!(res, ValidationResult::InvalidMove);
+        let res = chess.move_piece(Position::from_str("e1"), Position::from_str("a1"));
+        assert!(matches!(res, ValidationResult::Valid(_)));
+
+        // No castling out of check, even with the rights
+        let mut chess = Chess::from_fen("4r1k1/8/8/8/8/8/8/R3K2R w KQ", false).unwrap();
+        assert_eq!(chess.status(), Status::Check(Color::White));
+        let res = chess.move_piece(Position::from_str("e1"), Position::from_str("h1"));
+        assert_eq!(res, ValidationResult::InvalidMove);
+    }
+
     #[test]
     fn check_three_fold_repetition() {
-        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/K7 w").unwrap();
+        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/K7 w", false).unwrap();
         chess.move_piece(Position::from_str("a1"), Position::from_str("a2"));
         chess.move_piece(Position::from_str("a8"), Position::from_str("a7"));
         chess.move_piece(Position::from_str("a2"), Position::from_str("a1"));
@@ -172,16 +219,18 @@ mod tests {
         chess.move_piece(Position::from_str("a8"), Position::from_str("a7"));
         chess.move_piece(Position::from_str("a2"), Position::from_str("a1"));
 
-        assert_eq!(chess.status, Status::Chilling);
+        assert_eq!(chess.status(), Status::Chilling);
+        assert!(!chess.is_threefold_repetition());
 
         chess.move_piece(Position::from_str("a7"), Position::from_str("a8"));
 
-        assert_eq!(chess.status, Status::Draw(DrawType::ThreefoldRepetition));
+        assert_eq!(chess.status(), Status::Draw(DrawType::ThreefoldRepetition));
+        assert!(chess.is_threefold_repetition());
     }
 
     #[test]
     fn check_50_move_rule() {
-        let mut chess = Chess::from_fen("rrrrrrrr/8/8/8/8/8/8/RRRRRRRR w").unwrap();
+        let mut chess = Chess::from_fen("rrrrrrrr/8/8/8/8/8/8/RRRRRRRR w", false).unwrap();
         for i in 0..3 {
             for j in 0..8 {
                 chess.move_piece(Position { x: j, y: i }, Position { x: j, y: i + 1 });
@@ -213,19 +262,19 @@ mod tests {
             chess.move_piece(Position { x: i, y: 7 }, Position { x: i, y: 6 });
         }
 
-        assert_eq!(chess.status, Status::Draw(DrawType::FiftyMoveRule));
+        assert_eq!(chess.status(), Status::Draw(DrawType::FiftyMoveRule));
     }
 
     #[test]
     fn test_possible_moves() {
-        let chess = Chess::from_fen("k7/8/8/8/r7/8/7r/K7 w").unwrap();
+        let chess = Chess::from_fen("k7/8/8/8/r7/8/7r/K7 w", false).unwrap();
 
         let moves = chess.generate_valid_moves();
 
         assert_eq!(moves[0].len(), 1);
         assert_eq!(moves[0][0].to, Position::from_str("b1"));
 
-        let chess = Chess::from_fen("k2r1r2/8/8/8/8/8/8/R3K3 w").unwrap();
+        let chess = Chess::from_fen("k2r1r2/8/8/8/8/8/8/R3K3 w", false).unwrap();
 
         let moves = chess.generate_valid_moves();
 
@@ -234,4 +283,1674 @@ mod tests {
         assert_eq!(moves[4].len(), 1);
         assert_eq!(moves[4][0].to, Position::from_str("e2"));
     }
+
+    #[test]
+    fn check_validate_position() {
+        let chess = Chess::new();
+        assert!(chess.validate_position().is_empty());
+
+        let chess = Chess::from_fen("8/8/8/8/8/8/8/K7 w", false).unwrap();
+        assert_eq!(
+            chess.validate_position(),
+            vec![PositionIssue::MissingKing(Color::Black)]
+        );
+
+        let chess = Chess::from_fen("k6P/8/8/8/8/8/8/K7 b", false).unwrap();
+        let issues = chess.validate_position();
+        assert!(issues.contains(&PositionIssue::PawnOnBackRank(Position::from_str("h8"))));
+
+        let chess = Chess::from_fen("k6R/8/8/8/8/8/8/K7 w", false).unwrap();
+        assert_eq!(
+            chess.validate_position(),
+            vec![PositionIssue::OpponentInCheck(Color::Black)]
+        );
+
+        let chess = Chess::from_fen("k7/8/8/8/8/8/8/K7 w K e3", false).unwrap();
+        let issues = chess.validate_position();
+        assert!(issues.contains(&PositionIssue::InvalidCastlingRights(
+            CastlingType::KingSide(Color::White)
+        )));
+        assert!(issues.contains(&PositionIssue::InvalidEnPassant(Position::from_str("e3"))));
+
+        let chess = Chess::from_fen("k7/8/8/8/8/8/PPPPPPPP/KQQ5 w", false).unwrap();
+        assert_eq!(
+            chess.validate_position(),
+            vec![PositionIssue::TooManyPromotedPieces(Color::White)]
+        );
+
+        assert!(Chess::from_fen("kk6/8/8/8/8/8/8/K7 w", false).is_ok());
+        assert!(Chess::from_fen("kk6/8/8/8/8/8/8/K7 w", true).is_err());
+        assert!(Chess::from_fen(
+            "rnbqkbnr/pppp1ppp/8/4pP2/8/8/PPPPP1PP/RNBQKBNR w KQkq e6 0 3",
+            true
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn check_board_builder() {
+        let mut builder = BoardBuilder::new();
+        builder
+            .put(Position::from_str("e1"), PieceType::King, Color::White)
+            .put(Position::from_str("h1"), PieceType::Rook, Color::White)
+            .set_castling(CastlingRights {
+                white_king_side: true,
+                ..Default::default()
+            });
+
+        let issues = builder.build().unwrap_err();
+        assert_eq!(issues, vec![PositionIssue::MissingKing(Color::Black)]);
+
+        builder.put(Position::from_str("e8"), PieceType::King, Color::Black);
+        let mut chess = builder.build().unwrap();
+        let res = chess.move_piece(Position::from_str("e1"), Position::from_str("h1"));
+        assert_eq!(res, ValidationResult::Valid(Status::Chilling));
+
+        let mut builder = BoardBuilder::from_chess(&chess);
+        builder
+            .remove(Position::from_str("f1"))
+            .set_turn(Color::White);
+        assert!(builder.piece_at(Position::from_str("g1")).is_some());
+        assert!(builder.build().is_ok());
+
+        builder.clear();
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn check_accessors() {
+        let mut chess = Chess::from_fen("k7/4P3/8/8/8/8/8/K7 w - - 3 40", false).unwrap();
+        assert_eq!(chess.side_to_move(), Color::White);
+        assert_eq!(chess.halfmove_clock(), 3);
+        assert_eq!(chess.fullmove_number(), 40);
+        assert_eq!(chess.castling_rights(), CastlingRights::default());
+        assert_eq!(
+            chess.piece_at(Position::from_str("a8")).unwrap().piece_type,
+            PieceType::King
+        );
+
+        chess.move_piece(Position::from_str("e7"), Position::from_str("e8"));
+        assert_eq!(chess.status(), Status::AwaitingPromotion);
+        assert_eq!(
+            chess.pending_promotion().unwrap().position,
+            Position::from_str("e8")
+        );
+
+        chess.promote_piece(PieceType::Rook);
+        assert!(chess.pending_promotion().is_none());
+        assert_eq!(chess.status(), Status::Check(Color::Black));
+        assert_eq!(chess.winner(), None);
+        assert_eq!(chess.fullmove_number(), 40);
+    }
+
+    #[test]
+    fn check_display() {
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R3K3 w", false).unwrap();
+        assert_eq!(
+            chess.to_string(),
+            "  +-----------------+\n\
+             8 | . . . . k . . . |\n\
+             7 | . . . . . . . . |\n\
+             6 | . . . . . . . . |\n\
+             5 | . . . . . . . . |\n\
+             4 | . . . . . . . . |\n\
+             3 | . . . . . . . . |\n\
+             2 | . . . . . . . . |\n\
+             1 | R . . . K . . . |\n\
+             \x20 +-----------------+\n\
+             \x20   a b c d e f g h\n"
+        );
+
+        chess.move_piece(Position::from_str("a1"), Position::from_str("a8"));
+        let last_move = chess.last_move().unwrap();
+        assert_eq!(last_move.to_string(), "a1a8");
+        assert_eq!(last_move.piece.to_string(), "R");
+        assert_eq!(
+            format!("{} {}", Color::Black, PieceType::Knight),
+            "black knight"
+        );
+
+        let renderer = BoardRenderer {
+            style: PieceStyle::Unicode,
+            perspective: Color::Black,
+            coordinates: false,
+            highlight_last_move: true,
+            highlight_check: true,
+        };
+        let rendered = renderer.render(&chess);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[1], "│ · · · ♔ · · · \x1b[43m·\x1b[0m │");
+        assert_eq!(
+            lines[8],
+            "│ · · · \x1b[41m♚\x1b[0m · · · \x1b[43m♖\x1b[0m │"
+        );
+    }
+
+    #[test]
+    fn check_svg() {
+        use chess::svg::{Arrow, SvgRenderer};
+
+        let chess = Chess::from_fen("4k3/8/8/8/8/8/8/4R1K1 b", false).unwrap();
+        let renderer = SvgRenderer {
+            size: 400,
+            arrows: vec![Arrow::new(
+                Position::from_str("e1"),
+                Position::from_str("e8"),
+            )],
+            highlighted: vec![Position::from_str("e1")],
+            ..Default::default()
+        };
+        let svg = renderer.render(&chess);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("class=\"piece ").count(), 3);
+        assert_eq!(svg.matches("class=\"arrow\"").count(), 1);
+        assert_eq!(svg.matches("class=\"highlight\"").count(), 1);
+        // The black king on e8 is in check, drawn at the top of the board
+        assert!(svg.contains("class=\"check\" x=\"200\" y=\"0\""));
+
+        let renderer = SvgRenderer {
+            perspective: Color::Black,
+            check_marker: false,
+            coordinates: false,
+            ..Default::default()
+        };
+        let svg = renderer.render(&chess);
+        assert!(!svg.contains("class=\"check\""));
+        assert!(!svg.contains("<text"));
+        assert!(svg.contains("class=\"piece black king\" transform=\"translate(135,315)"));
+    }
+
+    #[test]
+    fn check_snapshot() {
+        let mut chess = Chess::from_fen("k7/8/8/8/8/8/8/K7 w", false).unwrap();
+        chess.move_piece(Position::from_str("a1"), Position::from_str("a2"));
+        chess.move_piece(Position::from_str("a8"), Position::from_str("a7"));
+        chess.move_piece(Position::from_str("a2"), Position::from_str("a1"));
+
+        let mut restored = Chess::from_snapshot(chess.snapshot()).unwrap();
+        assert_eq!(restored.snapshot(), chess.snapshot());
+
+        // The repetition history comes along, so the draw still happens on time
+        for (from, to) in [("a7", "a8"), ("a1", "a2"), ("a8", "a7"), ("a2", "a1")] {
+            assert_eq!(restored.status(), Status::Chilling);
+            restored.move_piece(Position::from_str(from), Position::from_str(to));
+        }
+        restored.move_piece(Position::from_str("a7"), Position::from_str("a8"));
+        assert_eq!(
+            restored.status(),
+            Status::Draw(DrawType::ThreefoldRepetition)
+        );
+
+        let mut snapshot = chess.snapshot();
+        snapshot.board.pop();
+        assert!(Chess::from_snapshot(snapshot).is_err());
+    }
+
+    #[test]
+    fn check_tampered_snapshot() {
+        let mut chess = Chess::from_fen("k7/4P3/8/8/8/8/8/K7 w", false).unwrap();
+        let original = chess.snapshot();
+
+        let mut snapshot = original.clone();
+        snapshot.status = Status::Checkmate(Color::Black);
+        snapshot.winner = Some(Color::White);
+        assert!(Chess::from_snapshot(snapshot).is_err());
+
+        // A position off the board that still gives the right index
+        let mut snapshot = original.clone();
+        let mut king = snapshot.board[0].take().unwrap();
+        king.position = Position { x: 9, y: 0 };
+        snapshot.board[9] = Some(king);
+        assert!(Chess::from_snapshot(snapshot).is_err());
+
+        let mut snapshot = original.clone();
+        snapshot.board[63] = Some(Piece::new(
+            PieceType::King,
+            Color::Black,
+            Position::from_str("h8"),
+        ));
+        assert!(Chess::from_snapshot(snapshot).is_err());
+
+        let mut snapshot = original.clone();
+        snapshot.en_passant = Some(Position { x: 0, y: 12 });
+        assert!(Chess::from_snapshot(snapshot).is_err());
+
+        let mut snapshot = original.clone();
+        snapshot.awaiting_promotion_piece = Some(Piece::new(
+            PieceType::Pawn,
+            Color::White,
+            Position { x: 0, y: 9 },
+        ));
+        snapshot.status = Status::AwaitingPromotion;
+        assert!(Chess::from_snapshot(snapshot).is_err());
+
+        // A real pending promotion is restored and can be finished
+        chess.move_piece(Position::from_str("e7"), Position::from_str("e8"));
+        let mut restored = Chess::from_snapshot(chess.snapshot()).unwrap();
+        assert_eq!(restored.status(), Status::AwaitingPromotion);
+        assert_eq!(
+            restored.promote_piece(PieceType::Queen),
+            Some(Status::Check(Color::Black))
+        );
+        assert_eq!(restored.snapshot(), {
+            chess.promote_piece(PieceType::Queen);
+            chess.snapshot()
+        });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn check_serde() {
+        let mut chess = Chess::new();
+        chess.move_piece(Position::from_str("e2"), Position::from_str("e4"));
+
+        let json = serde_json::to_string(&chess.snapshot()).unwrap();
+        let snapshot: ChessSnapshot = serde_json::from_str(&json).unwrap();
+        let restored = Chess::from_snapshot(snapshot).unwrap();
+        assert_eq!(restored.snapshot(), chess.snapshot());
+        assert_eq!(restored.en_passant(), Some(Position::from_str("e3")));
+
+        let json =
+            serde_json::to_string(&ValidationResult::Valid(Status::Check(Color::White))).unwrap();
+        assert_eq!(json, r#"{"Valid":{"Check":"White"}}"#);
+    }
+
+    #[test]
+    fn check_play_with_promotion() {
+        let mut chess = Chess::from_fen("k7/4P3/8/8/8/8/8/K7 w", false).unwrap();
+        let moves = chess.all_valid_moves();
+        // Three king moves and four different promotions
+        assert_eq!(moves.len(), 7);
+
+        let mut mov = moves
+            .iter()
+            .find(|m| m.promotion == Some(PieceType::Knight))
+            .unwrap()
+            .clone();
+        assert_eq!(mov.to_string(), "e7e8n");
+
+        mov.promotion = None;
+        assert_eq!(chess.play(&mov), ValidationResult::InvalidMove);
+        mov.promotion = Some(PieceType::King);
+        assert_eq!(chess.play(&mov), ValidationResult::InvalidMove);
+
+        mov.promotion = Some(PieceType::Queen);
+        assert_eq!(
+            chess.play(&mov),
+            ValidationResult::Valid(Status::Check(Color::Black))
+        );
+        assert_eq!(chess.last_move().unwrap().to_string(), "e7e8q");
+    }
+
+    #[test]
+    fn check_encoding() {
+        use chess::encoding::*;
+
+        let mov = CompactMove::new(
+            Position::from_str("b7"),
+            Position::from_str("a8"),
+            Some(PieceType::Rook),
+        );
+        assert_eq!(mov.from(), Position::from_str("b7"));
+        assert_eq!(mov.to(), Position::from_str("a8"));
+        assert_eq!(mov.promotion(), Some(PieceType::Rook));
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b Kq a3 4 17";
+        let chess = Chess::from_fen(fen, false).unwrap();
+        let bytes = chess.encode_position().unwrap();
+        let decoded = Chess::decode_position(&bytes).unwrap();
+        assert_eq!(decoded.board(), chess.board());
+        assert_eq!(decoded.side_to_move(), Color::Black);
+        assert_eq!(decoded.castling_rights(), chess.castling_rights());
+        assert_eq!(decoded.en_passant(), Some(Position::from_str("a3")));
+        assert_eq!(decoded.halfmove_clock(), 4);
+        assert_eq!(decoded.fullmove_number(), 17);
+
+        let mut illegal = bytes;
+        illegal[0] ^= 1;
+        assert!(Chess::decode_position(&illegal).is_err());
+
+        let start = Chess::from_fen("4k3/1P6/8/8/8/8/8/R3K3 w Q - 0 1", false).unwrap();
+        let mut chess = start.clone();
+        let mut moves = Vec::new();
+        for (from, to, promotion) in [
+            ("e1", "a1", None),
+            ("e8", "f7", None),
+            ("b7", "b8", Some(PieceType::Knight)),
+        ] {
+            let mut mov =
+                CompactMove::new(Position::from_str(from), Position::from_str(to), promotion)
+                    .to_move(&chess)
+                    .unwrap();
+            mov.promotion = promotion;
+            chess.play(&mov);
+            moves.push(mov);
+        }
+
+        let bytes = encode_game(&start, &moves).unwrap();
+        assert_eq!(bytes.len(), POSITION_BYTES + 2 + 3);
+        let (decoded_start, decoded_moves) = decode_game(&bytes).unwrap();
+        assert_eq!(decoded_start.board(), start.board());
+        assert_eq!(decoded_moves, moves);
+
+        let mut truncated = bytes.clone();
+        truncated.pop();
+        assert!(decode_game(&truncated).is_err());
+    }
+
+    #[test]
+    fn check_san() {
+        let chess = Chess::from_fen("r3k2r/8/8/8/8/8/8/R3K1NR w KQkq - 0 1", false).unwrap();
+        let mov = chess.parse_san("O-O-O").unwrap();
+        assert_eq!(chess.to_san(&mov), "O-O-O");
+        assert!(chess.parse_san("O-O").is_none());
+
+        let mov = chess.parse_san("Rb1").unwrap();
+        assert_eq!(mov.from, Position::from_str("a1"));
+        let mov = chess.parse_san("Nf3").unwrap();
+        assert_eq!(chess.to_san(&mov), "Nf3");
+
+        let chess = Chess::from_fen("7k/P7/8/8/8/1N3N2/8/K7 w - - 0 1", false).unwrap();
+        let mov = chess.parse_san("Nbd4").unwrap();
+        assert_eq!(mov.from, Position::from_str("b3"));
+        assert_eq!(chess.to_san(&mov), "Nbd4");
+        assert!(chess.parse_san("Nd4").is_none());
+
+        let mov = chess.parse_san("a8=Q+").unwrap();
+        assert_eq!(mov.promotion, Some(PieceType::Queen));
+        assert_eq!(chess.to_san(&mov), "a8=Q+");
+        assert_eq!(
+            chess.parse_san("a8N").unwrap().promotion,
+            Some(PieceType::Knight)
+        );
+    }
+
+    #[test]
+    fn check_game_tree() {
+        let mut tree = GameTree::new();
+        for san in ["e4", "e5", "Nf3"] {
+            let mov = tree.position().parse_san(san).unwrap();
+            tree.play(&mov).unwrap();
+        }
+        let nf3 = tree.current();
+        tree.go_back();
+        let nc3 = tree.position().parse_san("Nc3").unwrap();
+        let nc3 = tree.play(&nc3).unwrap();
+        let nf6 = tree.position().parse_san("Nf6").unwrap();
+        let nf6 = tree.play(&nf6).unwrap();
+
+        assert_eq!(tree.main_line().len(), 3);
+        assert_eq!(*tree.main_line().last().unwrap(), nf3);
+
+        assert!(tree.promote_variation(nc3));
+        assert_eq!(tree.main_line(), tree.line_to(nf6));
+        assert!(tree.make_main_line(nf3));
+        assert_eq!(*tree.main_line().last().unwrap(), nf3);
+
+        tree.set_comment(nc3, "Vienna");
+        tree.add_nag(nf3, 1);
+
+        let pgn = tree.to_pgn();
+        assert_eq!(pgn, "1. e4 e5 2. Nf3 $1 (2. Nc3 {Vienna} 2... Nf6) *\n");
+        let parsed = GameTree::from_pgn(&pgn).unwrap();
+        assert_eq!(parsed.to_pgn(), pgn);
+
+        assert!(tree.delete_variation(nc3));
+        assert_eq!(tree.current(), tree.line_to(nf3)[1]);
+        assert!(tree.node(nf6).is_none());
+        assert!(!tree.delete_variation(tree.root()));
+        assert_eq!(tree.to_pgn(), "1. e4 e5 2. Nf3 $1 *\n");
+
+        let pgn = "[Event \"Test\"]\n[Result \"1-0\"]\n\n1.e4 e5 2.Nf3!? (2.f4 exf4 (2...d5 3.exd5) 3.Nf3) 2...Nc6 {main} 1-0";
+        let tree = GameTree::from_pgn(pgn).unwrap();
+        assert_eq!(tree.tag("Event"), Some("Test"));
+        assert_eq!(tree.main_line().len(), 4);
+        assert_eq!(
+            tree.to_pgn(),
+            "[Event \"Test\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 $5 (2. f4 exf4 (2... d5 3. exd5) 3. Nf3) 2... Nc6 {main} 1-0\n"
+        );
+
+        // A comment at the start of a variation belongs to its first move
+        let tree = GameTree::from_pgn("1. e4 ({Alt} 1. d4) e5").unwrap();
+        let e4 = tree.main_line()[0];
+        let d4 = tree.node(tree.root()).unwrap().children()[1];
+        assert_eq!(tree.node(e4).unwrap().comment(), "");
+        assert_eq!(tree.node(d4).unwrap().pre_comment(), "Alt");
+        let pgn = tree.to_pgn();
+        assert_eq!(pgn, "1. e4 ({Alt} 1. d4) 1... e5 *\n");
+        assert_eq!(GameTree::from_pgn(&pgn).unwrap().to_pgn(), pgn);
+        // Without a move the comment is kept on the move the variation was for
+        let tree = GameTree::from_pgn("1. e4 {Good} ({note}) e5").unwrap();
+        assert_eq!(tree.to_pgn(), "1. e4 {Good note} 1... e5 *\n");
+
+        assert!(GameTree::from_pgn("1. e4 (1. d4 e5").is_err());
+        assert!(GameTree::from_pgn("1. e5").is_err());
+    }
+
+    #[test]
+    fn check_evaluate() {
+        let chess = Chess::new();
+        let terms = chess::eval::evaluate_terms(&chess);
+        assert_eq!(terms.total(), 0);
+        assert_eq!(terms.phase, chess::eval::MAX_PHASE);
+        assert_eq!(chess::eval::evaluate(&chess), 0);
+
+        // Black is missing the queen, which is good for white whoever is to move
+        let white = Chess::from_fen(
+            "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            false,
+        )
+        .unwrap();
+        let black = Chess::from_fen(
+            "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+            false,
+        )
+        .unwrap();
+        assert_eq!(chess::eval::evaluate_terms(&white).material, 900);
+        assert!(chess::eval::evaluate(&white) > 800);
+        assert_eq!(
+            chess::eval::evaluate(&black),
+            -chess::eval::evaluate(&white)
+        );
+
+        // The same position with the colors swapped gets the opposite score
+        let a = Chess::from_fen("4k3/pp3ppp/8/3P4/8/8/P4PPP/4K2R w K - 0 1", false).unwrap();
+        let b = Chess::from_fen("4k2r/p4ppp/8/8/3p4/8/PP3PPP/4K3 b k - 0 1", false).unwrap();
+        assert_eq!(
+            chess::eval::evaluate_terms(&a).total(),
+            -chess::eval::evaluate_terms(&b).total()
+        );
+        assert_eq!(chess::eval::evaluate(&a), chess::eval::evaluate(&b));
+        assert!(chess::eval::evaluate_terms(&a).pawn_structure > 0);
+
+        let mate = Chess::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", false).unwrap();
+        assert_eq!(chess::eval::evaluate(&mate), -chess::eval::MATE_SCORE);
+    }
+
+    #[test]
+    fn check_search() {
+        use chess::search::*;
+
+        let chess = Chess::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", false).unwrap();
+        let result = search(&chess, &SearchLimits::new());
+        let best = result.best_move.clone().unwrap();
+        assert_eq!(best.to, Position::from_str("a8"));
+        assert_eq!(result.mate_in(), Some(1));
+        assert_eq!(result.pv.len(), 1);
+
+        // Takes the undefended queen
+        let chess = Chess::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", false).unwrap();
+        let result = search(
+            &chess,
+            &SearchLimits {
+                depth: 3,
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.best_move.unwrap().to, Position::from_str("d5"));
+        assert!(result.score > 300);
+        assert_eq!(result.depth, 3);
+
+        // Depth 1 is always finished, even with a tiny node limit
+        let limits = SearchLimits {
+            depth: 10,
+            nodes: Some(1),
+            time: None,
+        };
+        let result = search(&Chess::new(), &limits);
+        assert!(result.best_move.is_some());
+        assert_eq!(result.depth, 1);
+
+        let mut searcher = Searcher::new();
+        let mut depths = Vec::new();
+        let limits = SearchLimits {
+            depth: 3,
+            ..Default::default()
+        };
+        let result = searcher.search_with_info(&Chess::new(), &limits, |r| depths.push(r.depth));
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert!(result.stats.nodes > 0);
+        assert!(chess_is_valid(&Chess::new(), &result.pv));
+
+        let mated = Chess::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", false).unwrap();
+        assert!(search(&mated, &SearchLimits::new()).best_move.is_none());
+    }
+
+    fn chess_is_valid(start: &Chess, moves: &[Move]) -> bool {
+        let mut chess = start.clone();
+        moves
+            .iter()
+            .all(|m| matches!(chess.play(m), ValidationResult::Valid(_)))
+    }
+
+    #[test]
+    fn check_bot() {
+        let depths: Vec<u8> = SkillLevel::ALL.iter().map(|l| l.settings().depth).collect();
+        assert!(depths.windows(2).all(|d| d[0] <= d[1]));
+        assert!(SkillLevel::Beginner.rating_band().1 <= SkillLevel::Novice.rating_band().1);
+
+        let chess = Chess::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", false).unwrap();
+        let mut bot = Bot::new(SkillLevel::Advanced).with_seed(7);
+        assert_eq!(
+            bot.choose_move(&chess).unwrap().to,
+            Position::from_str("a8")
+        );
+
+        // The same seed gives the same moves
+        let chess = Chess::new();
+        let mut a = Bot::new(SkillLevel::Beginner).with_seed(42);
+        let mut b = Bot::new(SkillLevel::Beginner).with_seed(42);
+        for _ in 0..5 {
+            let mov = a.choose_move(&chess).unwrap();
+            assert_eq!(Some(&mov), b.choose_move(&chess).as_ref());
+            assert!(chess.validate_move(mov.from, mov.to) != ValidationResult::InvalidMove);
+        }
+
+        // Mistakes are never worse than allowed, so a free queen is always taken
+        let chess = Chess::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", false).unwrap();
+        let settings = chess::bot::BotSettings {
+            depth: 2,
+            eval_noise: 10,
+            mistake_chance: 1.0,
+            max_mistake: 50,
+            time: None,
+        };
+        let mut bot = Bot::with_settings(settings).with_seed(3);
+        assert_eq!(
+            bot.choose_move(&chess).unwrap().to,
+            Position::from_str("d5")
+        );
+
+        let mated = Chess::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", false).unwrap();
+        assert!(bot.choose_move(&mated).is_none());
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn check_uci() {
+        use chess::uci::*;
+
+        assert_eq!(
+            parse_command("position startpos moves e2e4 e7e5"),
+            UciCommand::Position {
+                fen: None,
+                moves: vec!["e2e4".to_string(), "e7e5".to_string()]
+            }
+        );
+        assert_eq!(
+            parse_command("setoption name Clear Hash"),
+            UciCommand::SetOption {
+                name: "Clear Hash".to_string(),
+                value: None
+            }
+        );
+        let params = match parse_command("go wtime 60000 btime 1000 winc 1000 binc 0") {
+            UciCommand::Go(params) => params,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(params.wtime, Some(60000));
+        assert_eq!(
+            params.to_command(),
+            "go wtime 60000 btime 1000 winc 1000 binc 0"
+        );
+        let white = params.limits(Color::White).time.unwrap();
+        let black = params.limits(Color::Black).time.unwrap();
+        assert!(white > black && black.as_millis() <= 500);
+
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let chess = position_from_command(Some(fen), &["e1g1".to_string()]).unwrap();
+        assert!(chess.piece_at(Position::from_str("g1")).is_some());
+        let chess = Chess::from_fen(fen, false).unwrap();
+        let castling = parse_uci_move(&chess, "e1c1").unwrap();
+        assert_eq!(castling.to, Position::from_str("a1"));
+        assert_eq!(move_to_uci(&chess, &castling), "e1c1");
+        assert!(position_from_command(None, &["e2e5".to_string()]).is_none());
+
+        let output = SharedBuffer::default();
+        let input =
+            "uci\nisready\nposition fen 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\ngo depth 3\nquit\n";
+        run(input.as_bytes(), output.clone());
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"uciok"));
+        assert!(lines.contains(&"readyok"));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("info depth 1 score mate 1")));
+        assert_eq!(*lines.last().unwrap(), "bestmove a1a8");
+
+        let output = SharedBuffer::default();
+        run("go infinite\nstop\n".as_bytes(), output.clone());
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(text.lines().last().unwrap().starts_with("bestmove "));
+
+        // The default and an empty value mean no tablebases, a missing directory is reported
+        let output = SharedBuffer::default();
+        let input = "setoption name SyzygyPath value <empty>\nsetoption name SyzygyPath value\n\
+                     setoption name SyzygyPath value /no/such/tablebases\nquit\n";
+        run(input.as_bytes(), output.clone());
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(text, "info string could not read tablebase directory\n");
+
+        // A new position stops an infinite search instead of waiting for it forever
+        let output = SharedBuffer::default();
+        let thread_output = output.clone();
+        let (done, finished) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let input = "go infinite\nposition startpos\nisready\nquit\n";
+            run(input.as_bytes(), thread_output);
+            done.send(()).unwrap();
+        });
+        assert!(finished
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .is_ok());
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.iter().any(|l| l.starts_with("bestmove ")));
+        assert_eq!(*lines.last().unwrap(), "readyok");
+    }
+
+    #[cfg(unix)]
+    fn fake_engine(name: &str, script: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "chess-fake-engine-{}-{}.sh",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, script).unwrap();
+        path
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn check_engine_client() {
+        use chess::engine::*;
+        use chess::uci::GoParams;
+
+        let script = fake_engine(
+            "scripted",
+            r#"
+while read line; do
+  case "$line" in
+    uci) echo "id name Fake Engine"; echo "id author Test"
+         echo "option name Hash type spin default 16 min 1 max 64"
+         echo "option name Clear Hash type button"; echo uciok ;;
+    isready) echo readyok ;;
+    go*) echo "info string thinking"
+         echo "info depth 1 score cp 20 nodes 10 pv e2e4"
+         echo "info depth 2 score mate -3 upperbound nodes 40 time 5 pv e2e4 e7e5 e1e3"
+         echo "bestmove e2e4 ponder e7e5" ;;
+    quit) exit 0 ;;
+  esac
+done
+"#,
+        );
+        let mut engine = Engine::spawn("sh", &[script.to_str().unwrap()]).unwrap();
+        assert_eq!(engine.name.as_deref(), Some("Fake Engine"));
+        assert_eq!(engine.options.len(), 2);
+        assert_eq!(engine.options[0].name, "Hash");
+        assert_eq!(engine.options[0].default.as_deref(), Some("16"));
+        assert_eq!(engine.options[1].name, "Clear Hash");
+
+        engine.set_option("Hash", Some("32")).unwrap();
+        engine.new_game().unwrap();
+        engine.set_position(&Chess::new()).unwrap();
+        let params = GoParams {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let result = engine.go(&params).unwrap();
+        assert_eq!(result.best_move.unwrap().to, Position::from_str("e4"));
+        assert_eq!(result.ponder.unwrap().to, Position::from_str("e5"));
+        let info = result.info.unwrap();
+        assert_eq!(info.depth, Some(2));
+        assert_eq!(info.score, Some(Score::Mate(-3)));
+        assert_eq!(info.bound, Some(ScoreBound::Upper));
+        // The last move of the pv is not valid and is left out
+        assert_eq!(info.pv.len(), 2);
+        engine.quit().unwrap();
+
+        // A search on the clock may take longer than the reply timeout
+        let slow = fake_engine(
+            "slow",
+            r#"
+while read line; do
+  case "$line" in
+    uci) echo uciok ;;
+    isready) echo readyok ;;
+    go*) sleep 1; echo "bestmove e2e4" ;;
+    stop) echo "bestmove d2d4" ;;
+    quit) exit 0 ;;
+  esac
+done
+"#,
+        );
+        let mut engine = Engine::spawn_with_timeout(
+            "sh",
+            &[slow.to_str().unwrap()],
+            std::time::Duration::from_millis(300),
+        )
+        .unwrap();
+        engine.set_position(&Chess::new()).unwrap();
+        let params = GoParams {
+            wtime: Some(60000),
+            btime: Some(60000),
+            ..Default::default()
+        };
+        let result = engine.go(&params).unwrap();
+        assert_eq!(result.best_move.unwrap().to, Position::from_str("e4"));
+        engine.quit().unwrap();
+
+        let silent = fake_engine("silent", "while read line; do :; done\n");
+        let res = Engine::spawn_with_timeout(
+            "sh",
+            &[silent.to_str().unwrap()],
+            std::time::Duration::from_millis(200),
+        );
+        assert!(matches!(res, Err(EngineError::Timeout)));
+
+        assert!(matches!(
+            Engine::spawn("/nonexistent/engine", &[]),
+            Err(EngineError::Spawn(_))
+        ));
+
+        let _ = std::fs::remove_file(script);
+        let _ = std::fs::remove_file(slow);
+        let _ = std::fs::remove_file(silent);
+    }
+
+    #[test]
+    fn check_xboard() {
+        let input = "xboard\nprotover 2\nnew\nsd 2\nping 1\nusermove e2e4\nusermove e2e4\nforce\nundo\nundo\nsetboard 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\ngo\nquit\n";
+        let output = SharedBuffer::default();
+        chess::xboard::run(input.as_bytes(), output.clone());
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines[0].starts_with("feature ") && lines[0].ends_with("done=1"));
+        assert_eq!(lines[1], "pong 1");
+        // The engine plays black and answers e2e4, after that e2e4 is not valid anymore
+        assert!(lines[2].starts_with("move "));
+        assert_eq!(lines[3], "Illegal move: e2e4");
+        assert_eq!(lines[4], "move a1a8");
+        assert_eq!(lines[5], "1-0 {White mates}");
+        assert_eq!(lines.len(), 6);
+
+        // `?` makes the engine move right away, and ping is answered after the move
+        let output = SharedBuffer::default();
+        let thread_output = output.clone();
+        let (done, finished) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let input = "new\nst 60\ntime 6000\notim 6000\ngo\n?\nping 2\nquit\n";
+            chess::xboard::run(input.as_bytes(), thread_output);
+            done.send(()).unwrap();
+        });
+        assert!(finished
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .is_ok());
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("move "));
+        assert_eq!(lines[1], "pong 2");
+    }
+
+    #[test]
+    fn check_polyglot() {
+        use chess::polyglot::{encode_move, polyglot_key, Book, BookEntry};
+
+        // The keys from the Polyglot documentation
+        let keys = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                0x463b96181691fc9c,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+                0x823c9b50fd114196,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+                0x0756b94461c50fb0,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+                0x662fafb965db29d4,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                0x22a48b5a8e47ff78,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+                0x652a607ca3f242c1,
+            ),
+            (
+                "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+                0x00fdd303c946bdd9,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+                0x3c8123ea7b067637,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+                0x5c3f9b829b279560,
+            ),
+        ];
+        for (fen, key) in keys {
+            assert_eq!(
+                polyglot_key(&Chess::from_fen(fen, false).unwrap()),
+                key,
+                "{}",
+                fen
+            );
+        }
+
+        let start = Chess::new();
+        let e4 = start.parse_san("e4").unwrap();
+        let d4 = start.parse_san("d4").unwrap();
+        // e1h1, the king taking its own rook
+        let castling = Chess::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", false).unwrap();
+        let short = castling.parse_san("O-O").unwrap();
+        assert_eq!(encode_move(&short), 0x0107);
+
+        let entry = |chess: &Chess, mov: &Move, weight| BookEntry {
+            key: polyglot_key(chess),
+            raw_move: encode_move(mov),
+            weight,
+            learn: 0,
+        };
+        let book = Book::from_entries(vec![
+            entry(&castling, &short, 1),
+            entry(&start, &d4, 1),
+            entry(&start, &e4, 3),
+        ]);
+        let book = Book::from_bytes(&book.to_bytes()).unwrap();
+        assert_eq!(book.len(), 3);
+        assert!(Book::from_bytes(&[0; 15]).is_err());
+
+        assert_eq!(book.moves(&start), vec![(e4.clone(), 3), (d4.clone(), 1)]);
+        assert_eq!(book.best_move(&start), Some(e4.clone()));
+        assert_eq!(book.weighted_move(&start, 2), Some(e4.clone()));
+        assert_eq!(book.weighted_move(&start, 3), Some(d4.clone()));
+        assert_eq!(book.best_move(&castling), Some(short));
+        assert!(book
+            .moves(&Chess::from_fen("8/8/8/8/8/8/8/K6k w - - 0 1", false).unwrap())
+            .is_empty());
+
+        let mut bot = Bot::new(SkillLevel::Beginner).with_seed(5);
+        bot.book = Some(book);
+        let mov = bot.choose_move(&start).unwrap();
+        assert!(mov == e4 || mov == d4);
+    }
+
+    #[test]
+    fn check_book_builder() {
+        use chess::polyglot::{Book, BookBuilder, MoveStats};
+
+        let pgn = r#"[White "A"]
+[WhiteElo "2100"]
+[BlackElo "2000"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[WhiteElo "1500"]
+[BlackElo "2000"]
+[Result "0-1"]
+
+1. e4 c5 0-1
+
+[WhiteElo "2200"]
+[BlackElo "2200"]
+
+1. d4 d5 1/2-1/2
+
+[Result "*"]
+
+1. c4 *
+
+1. e4 Ke7?? (1... Kxe2) 1-0
+
+[Result "1-0"]
+
+1. Nf3 e5 2. Nxe5 1-0
+"#;
+        let games = chess::pgn::read_games(pgn);
+        assert_eq!(games.len(), 6);
+        assert!(games[4].is_err());
+        assert_eq!(games[5].as_ref().unwrap().main_line().len(), 3);
+
+        let mut builder = BookBuilder::new();
+        assert_eq!(builder.add_pgn(pgn), 4);
+
+        let start = Chess::new();
+        let e4 = start.parse_san("e4").unwrap();
+        let stats = builder.stats(&start);
+        assert_eq!(
+            stats[0],
+            (
+                e4.clone(),
+                MoveStats {
+                    wins: 1,
+                    draws: 0,
+                    losses: 1
+                }
+            )
+        );
+        assert_eq!(stats.len(), 3);
+
+        let book = Book::from_bytes(&builder.build().to_bytes()).unwrap();
+        let weights: Vec<u16> = book.moves(&start).iter().map(|(_, w)| *w).collect();
+        assert_eq!(weights, vec![2, 2, 1]);
+
+        let mut after_e4 = start.clone();
+        after_e4.play(&e4);
+        // c5 won and e5 lost, so only c5 is in the book
+        assert_eq!(
+            book.moves(&after_e4),
+            vec![(after_e4.parse_san("c5").unwrap(), 2)]
+        );
+
+        let mut strong = BookBuilder::new();
+        strong.min_rating = Some(2000);
+        strong.max_ply = 1;
+        assert_eq!(strong.add_pgn(pgn), 2);
+        let book = strong.build();
+        assert!(book.moves(&after_e4).is_empty());
+        assert_eq!(book.len(), 2);
+
+        let mut common = BookBuilder::new();
+        common.min_games = 2;
+        common.add_pgn(pgn);
+        // Only e4 was played twice, and it scored a point
+        assert_eq!(common.build().moves(&start), vec![(e4, 2)]);
+    }
+
+    // A KQvK table where white wins from every position except one, and black always loses.
+    // The pieces are stored white king, white queen, black king.
+    fn syzygy_tables(dir: &std::path::Path) {
+        fn align(data: &mut Vec<u8>, to: usize) {
+            while !data.len().is_multiple_of(to) {
+                data.push(0);
+            }
+        }
+
+        let mut wdl = vec![0xD7, 0x66, 0x0C, 0xA5, 1, 0x00, 0x66, 0x55, 0xEE];
+        align(&mut wdl, 2);
+        // White to move: blocks of 4096 bytes, one span, one block with 31332 values
+        wdl.extend([0, 12, 15, 0, 1, 0, 0, 0, 1, 1]);
+        // The lowest symbol of length 1, then two symbols standing for win and draw
+        wdl.extend([0, 0, 2, 0, 4, 0xF0, 0xFF, 2, 0xF0, 0xFF]);
+        // Black to move: always a loss
+        wdl.extend([0x80, 0]);
+        // The sparse index and the block length
+        wdl.extend([0, 0, 0, 0, 0x00, 0x40]);
+        wdl.extend(31331u16.to_le_bytes());
+        align(&mut wdl, 64);
+        let mut block = vec![0u8; 4096];
+        // Kb1, Qa1 and Kg8 is position 60 of the table
+        block[7] = 0x08;
+        wdl.extend(block);
+        std::fs::write(dir.join("KQvK.rtbw"), wdl).unwrap();
+
+        // Only white to move, always 4 moves to zeroing
+        let mut dtz = vec![0x71, 0xE8, 0x23, 0x5D, 1, 0x00, 0x06, 0x05, 0x0E];
+        align(&mut dtz, 2);
+        dtz.extend([0x80, 4]);
+        std::fs::write(dir.join("KQvK.rtbz"), dtz).unwrap();
+
+        std::fs::write(dir.join("KRvK.rtbw"), [0, 0, 0, 0]).unwrap();
+    }
+
+    #[test]
+    fn check_syzygy() {
+        use chess::search::{SearchLimits, Searcher};
+        use chess::syzygy::{Tablebase, Wdl};
+        use std::sync::Arc;
+
+        let dir = std::env::temp_dir().join(format!("chess-syzygy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        syzygy_tables(&dir);
+
+        let tablebase = Tablebase::open(&dir).unwrap();
+        assert_eq!(tablebase.max_pieces(), 3);
+        let probe = |fen: &str| {
+            let chess = Chess::from_fen(fen, false).unwrap();
+            (tablebase.probe_wdl(&chess), tablebase.probe_dtz(&chess))
+        };
+
+        assert_eq!(
+            probe("8/7k/8/8/8/8/8/QK6 w - - 0 1"),
+            (Some(Wdl::Win), Some(9))
+        );
+        assert_eq!(
+            probe("6k1/8/8/8/8/8/8/QK6 w - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+        // The same position with the colors swapped
+        assert_eq!(
+            probe("qk6/8/8/8/8/8/8/6K1 b - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+        // The table only has white to move, so the moves of black are looked at
+        assert_eq!(
+            probe("8/7k/8/8/8/8/8/QK6 b - - 0 1"),
+            (Some(Wdl::Loss), Some(-10))
+        );
+        // Black takes the queen
+        assert_eq!(probe("8/8/8/8/8/8/1k6/Q6K b - - 0 1").0, Some(Wdl::Draw));
+        assert_eq!(
+            probe("8/8/8/4k3/8/8/8/4K3 w - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+        // A broken table, one that is missing and castling rights
+        assert_eq!(probe("8/7k/8/8/8/8/8/RK6 w - - 0 1"), (None, None));
+        assert_eq!(probe("8/7k/8/8/8/8/8/BK6 w - - 0 1"), (None, None));
+        assert_eq!(probe("7k/8/8/8/8/8/8/4K2R w K - 0 1"), (None, None));
+
+        // Only Kg8 keeps the draw
+        let chess = Chess::from_fen("8/7k/8/8/8/8/8/QK6 b - - 0 1", false).unwrap();
+        let kg8 = chess.parse_san("Kg8").unwrap();
+        assert_eq!(tablebase.root_moves(&chess), Some(vec![kg8.clone()]));
+        assert_eq!(tablebase.best_move(&chess), Some(kg8.clone()));
+
+        let mut searcher = Searcher::new();
+        searcher.tablebase = Some(Arc::new(tablebase));
+        let result = searcher.search(
+            &chess,
+            &SearchLimits {
+                depth: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.best_move, Some(kg8));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    // Pawn tables with a made up value for every index, so a position has to give the same
+    // value as the one with the colors swapped. KPvK has a DTZ table with only white to move.
+    fn syzygy_pawn_tables(dir: &std::path::Path) {
+        fn align(data: &mut Vec<u8>, to: usize) {
+            while !data.len().is_multiple_of(to) {
+                data.push(0);
+            }
+        }
+
+        // One bit for each of the `size` values of a file, 0 stands for the first value
+        // of the side and 1 for the second. The spans and blocks have 32768 values.
+        fn add_bits(data: &mut Vec<u8>, files: usize, size: usize, values: &[[u8; 2]]) {
+            align(data, 2);
+            let blocks = size.div_ceil(32768);
+            for _ in 0..files {
+                for value in values {
+                    data.extend([0, 12, 15, 0]);
+                    data.extend((blocks as u32).to_le_bytes());
+                    data.extend([1, 1, 0, 0, 2, 0, value[0], 0xF0, 0xFF, value[1], 0xF0, 0xFF]);
+                }
+            }
+            let parts = files * values.len();
+            for _ in 0..parts {
+                for block in 0..blocks {
+                    data.extend((block as u32).to_le_bytes());
+                    data.extend([0x00, 0x40]);
+                }
+            }
+            for _ in 0..parts {
+                for block in 0..blocks {
+                    let length = (size - block * 32768).min(32768) - 1;
+                    data.extend((length as u16).to_le_bytes());
+                }
+            }
+            let mut random = size as u64;
+            for _ in 0..parts {
+                align(data, 64);
+                for _ in 0..blocks * 4096 {
+                    random = random
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    data.push((random >> 56) as u8);
+                }
+            }
+        }
+
+        // White pawn, white king, black king with the pawn on 6 ranks.
+        // White to move wins or draws, black to move loses or draws.
+        let mut wdl = vec![0xD7, 0x66, 0x0C, 0xA5, 3];
+        for _ in 0..4 {
+            wdl.extend([0x00, 0x11, 0x66, 0xEE]);
+        }
+        add_bits(&mut wdl, 4, 6 * 63 * 62, &[[4, 2], [0, 2]]);
+        std::fs::write(dir.join("KPvK.rtbw"), wdl).unwrap();
+
+        // White to move, one value mapped to 3 + file on the a and b files
+        // and to 300 + file with wide maps on the c and d files
+        let mut dtz = vec![0x71, 0xE8, 0x23, 0x5D, 3];
+        for _ in 0..4 {
+            dtz.extend([0x00, 0x01, 0x06, 0x0E]);
+        }
+        align(&mut dtz, 2);
+        dtz.extend([0x82, 1, 0x82, 1, 0x92, 1, 0x92, 1]);
+        for file in 0..2 {
+            dtz.extend([2, 0, 3 + file, 2, 0, 4 + file, 1, 0, 1, 0]);
+        }
+        for file in 0..2 {
+            align(&mut dtz, 2);
+            let maps: [&[u16]; 4] = [&[2, 0, 300 + file], &[2, 0, 400 + file], &[1, 0], &[1, 0]];
+            for value in maps.concat() {
+                dtz.extend(value.to_le_bytes());
+            }
+        }
+        std::fs::write(dir.join("KPvK.rtbz"), dtz).unwrap();
+
+        // White pawn, black pawn, white king, black king, only stored with white to move
+        let mut wdl = vec![0xD7, 0x66, 0x0C, 0xA5, 2];
+        for _ in 0..4 {
+            wdl.extend([0x00, 0x11, 0x11, 0x99, 0x66, 0xEE]);
+        }
+        add_bits(&mut wdl, 4, 6 * 47 * 62 * 61, &[[4, 0]]);
+        std::fs::write(dir.join("KPvKP.rtbw"), wdl).unwrap();
+    }
+
+    #[test]
+    fn check_syzygy_pawns() {
+        use chess::syzygy::{Tablebase, Wdl};
+
+        let dir = std::env::temp_dir().join(format!("chess-syzygy-pawns-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        syzygy_pawn_tables(&dir);
+
+        let tablebase = Tablebase::open(&dir).unwrap();
+        assert_eq!(tablebase.max_pieces(), 4);
+        // The position with the pieces, and the same position with the colors swapped
+        let positions = |pieces: &[(char, usize)], white: bool| {
+            let fen = |pieces: &[(char, usize)], white: bool| {
+                let mut board = ['1'; 64];
+                for (piece, square) in pieces {
+                    board[*square] = *piece;
+                }
+                let ranks: Vec<String> = board
+                    .chunks(8)
+                    .rev()
+                    .map(|rank| rank.iter().collect())
+                    .collect();
+                let side = if white { "w" } else { "b" };
+                format!("{} {} - - 0 1", ranks.join("/"), side)
+            };
+            let swapped: Vec<(char, usize)> = pieces
+                .iter()
+                .map(|(piece, square)| {
+                    let piece = if piece.is_ascii_uppercase() {
+                        piece.to_ascii_lowercase()
+                    } else {
+                        piece.to_ascii_uppercase()
+                    };
+                    (piece, square ^ 56)
+                })
+                .collect();
+            let chess = Chess::from_fen(&fen(pieces, white), true).ok()?;
+            let other = Chess::from_fen(&fen(&swapped, !white), true).unwrap();
+            Some((chess, other))
+        };
+
+        let mut wdl = Vec::new();
+        let mut dtz = Vec::new();
+        for pawn in (8..48).step_by(3) {
+            for (king, other_king) in [(0, 63), (4, 60), (27, 45), (39, 16)] {
+                for white in [true, false] {
+                    let pieces = [('P', pawn), ('K', king), ('k', other_king)];
+                    let Some((chess, other)) = positions(&pieces, white) else {
+                        continue;
+                    };
+                    // The black pawn is the flipped position
+                    assert_eq!(tablebase.probe_wdl(&chess), tablebase.probe_wdl(&other));
+                    assert_eq!(tablebase.probe_dtz(&chess), tablebase.probe_dtz(&other));
+                    wdl.push(tablebase.probe_wdl(&chess));
+                    dtz.push(tablebase.probe_dtz(&chess));
+                }
+            }
+        }
+        for value in [Wdl::Win, Wdl::Draw, Wdl::Loss] {
+            assert!(wdl.contains(&Some(value)));
+        }
+        assert!(!dtz.contains(&None));
+        // Mapped values with white to move, and with black to move one more
+        for value in [7, 9, 601, 603, -8, -10, -602, -604] {
+            assert!(dtz.contains(&Some(value)), "{value}");
+        }
+
+        let mut wdl = Vec::new();
+        for (pawn, other_pawn) in [(8, 48), (12, 52), (19, 42), (26, 35), (29, 46), (15, 39)] {
+            for (king, other_king) in [(0, 63), (6, 57), (27, 45)] {
+                for white in [true, false] {
+                    let pieces = [
+                        ('P', pawn),
+                        ('p', other_pawn),
+                        ('K', king),
+                        ('k', other_king),
+                    ];
+                    let Some((chess, other)) = positions(&pieces, white) else {
+                        continue;
+                    };
+                    // Black to move is the flipped position in the symmetric table
+                    assert_eq!(tablebase.probe_wdl(&chess), tablebase.probe_wdl(&other));
+                    wdl.push(tablebase.probe_wdl(&chess));
+                }
+            }
+        }
+        assert!(wdl.contains(&Some(Wdl::Win)) && wdl.contains(&Some(Wdl::Loss)));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn check_eco() {
+        use chess::eco::{classify, lookup};
+
+        let game = GameTree::from_pgn("1. e4 e5 2. Nf3 Nc6 3. Bb5 Nf6 4. d3 Bc5 5. c3 *").unwrap();
+        let opening = classify(&game).unwrap();
+        assert_eq!(opening.to_string(), "C65 Ruy Lopez, Berlin Defence");
+        assert_eq!(opening.ply, 6);
+
+        // A transposition to the same position
+        let game = GameTree::from_pgn("1. e4 e5 2. Bb5 Nf6 3. Nf3 Nc6 *").unwrap();
+        assert_eq!(classify(&game).unwrap().eco, "C65");
+        let game = GameTree::from_pgn("1. d4 d5 2. c4 c6 3. Nf3 Nf6 4. Nc3 e6 5. Bg5 *").unwrap();
+        let opening = classify(&game).unwrap();
+        assert_eq!((opening.eco.as_str(), opening.ply), ("D43", 8));
+
+        // Castling and checks in the moves
+        let game = GameTree::from_pgn(
+            "1. e4 e5 2. Nf3 Nc6 3. Bb5 Nf6 4. O-O Nxe4 5. d4 Nd6 6. Bxc6 dxc6 7. dxe5 Nf5 8. Qxd8+ Kxd8 *",
+        )
+        .unwrap();
+        assert_eq!(
+            classify(&game).unwrap().name,
+            "Ruy Lopez, Berlin Defence, Berlin Wall"
+        );
+
+        assert_eq!(classify(&GameTree::new()), None);
+        let game = GameTree::from_pgn("1. a3 *").unwrap();
+        assert_eq!(classify(&game), None);
+
+        let mut chess = Chess::new();
+        chess.play(&chess.parse_san("c4").unwrap());
+        let opening = lookup(&chess).unwrap();
+        assert_eq!((opening.eco.as_str(), opening.ply), ("A10", 1));
+    }
+
+    #[test]
+    fn check_mate_solver() {
+        use chess::mate::solve_mate;
+
+        let chess = Chess::from_fen("k7/8/2K5/8/8/8/8/1R6 w - - 0 1", false).unwrap();
+        assert!(solve_mate(&chess, 1).keys.is_empty());
+
+        // 1. Kc7 Ka7 2. Ra1#, while 1. Rb7 is stalemate
+        let solution = solve_mate(&chess, 2);
+        assert!(solution.is_unique());
+        let key = &solution.keys[0];
+        assert_eq!(chess.to_san(&key.mov), "Kc7");
+        assert_eq!(key.defences.len(), 1);
+        assert_eq!(key.defences[0].answers.len(), 1);
+        let answer = &key.defences[0].answers[0];
+        assert_eq!(answer.mov.to, Position::from_str("a1"));
+        assert!(answer.defences.is_empty());
+
+        // Any rook move along the first rank or up the h-file mates next move as well
+        let chess = Chess::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1", false).unwrap();
+        let intended = chess.parse_san("Rh8").unwrap();
+        let solution = solve_mate(&chess, 1);
+        assert!(solution.is_unique());
+        assert!(solution.cooks(&intended).is_empty());
+        let solution = solve_mate(&chess, 2);
+        assert!(!solution.is_unique());
+        assert_eq!(solution.cooks(&intended).len(), 11);
+        let rg1 = solution
+            .keys
+            .iter()
+            .find(|key| chess.to_san(&key.mov) == "Rg1")
+            .unwrap();
+        assert!(rg1
+            .defences
+            .iter()
+            .all(|defence| !defence.answers.is_empty()));
+    }
+
+    #[test]
+    fn check_puzzles() {
+        use chess::puzzle::{PuzzleFinder, Theme};
+        use chess::search::SearchLimits;
+
+        let mut finder = PuzzleFinder::new();
+        finder.limits = SearchLimits {
+            depth: 3,
+            ..SearchLimits::new()
+        };
+
+        // White misses the mate, then takes the rook left hanging
+        let game = GameTree::from_pgn(
+            "[FEN \"4r1k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1\"]\n\n1... Re7 2. h3 Rd7 *",
+        )
+        .unwrap();
+        let puzzles = finder.find(&game);
+        assert_eq!(puzzles.len(), 2);
+        let mate = &puzzles[0];
+        assert_eq!(mate.fen, "6k1/4rppp/8/8/8/8/5PPP/3R2K1 w - - 1 2");
+        assert_eq!(mate.san, vec!["Rd8+", "Re8", "Rxe8#"]);
+        assert_eq!(mate.uci, vec!["d1d8", "e7e8", "d8e8"]);
+        assert!(mate.missed);
+        assert_eq!(mate.themes, vec![Theme::Mate, Theme::BackRank]);
+        assert_eq!(puzzles[1].san, vec!["Rxd7"]);
+        assert!(puzzles[1].themes.is_empty());
+
+        let game = GameTree::from_pgn(
+            "[FEN \"r7/4k1pp/8/1N6/8/8/6PP/6K1 b - - 0 1\"]\n\n1... Ke8 2. Nc7+ *",
+        )
+        .unwrap();
+        let puzzles = finder.find(&game);
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].node, game.main_line()[0]);
+        assert_eq!(puzzles[0].uci, vec!["b5c7", "e8e7", "c7a8"]);
+        assert!(!puzzles[0].missed);
+        assert_eq!(puzzles[0].themes, vec![Theme::Fork]);
+
+        let game = GameTree::from_pgn(
+            "[FEN \"4k3/8/8/2q5/8/8/5PPP/R4K2 b - - 0 1\"]\n\n1... Qe5 2. Re1 *",
+        )
+        .unwrap();
+        let puzzles = finder.find(&game);
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].san[0], "Re1");
+        assert_eq!(puzzles[0].themes, vec![Theme::Pin]);
+        assert_eq!(Theme::BackRank.to_string(), "back-rank");
+
+        // Nothing decisive happens
+        let game =
+            GameTree::from_pgn("[FEN \"4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. Kd2 Kd8 *")
+                .unwrap();
+        assert!(finder.find(&game).is_empty());
+    }
+
+    #[test]
+    fn check_annotation() {
+        use chess::annotate::{Annotator, NAG_BLUNDER, NAG_GOOD};
+        use chess::search::SearchLimits;
+
+        let mut annotator = Annotator::new();
+        annotator.limits = SearchLimits {
+            depth: 3,
+            ..SearchLimits::new()
+        };
+
+        let mut game = GameTree::from_pgn(
+            "[FEN \"4r1k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1\"]\n\n1... Re7 2. h3 Rd7 3. Rxd7 h6 *",
+        )
+        .unwrap();
+        let annotation = annotator.annotate(&mut game);
+        let nags: Vec<Option<u8>> = annotation.moves.iter().map(|m| m.nag).collect();
+        assert_eq!(
+            nags,
+            vec![
+                Some(NAG_BLUNDER),
+                Some(NAG_BLUNDER),
+                Some(NAG_BLUNDER),
+                Some(NAG_GOOD),
+                None
+            ]
+        );
+        // Missing the mate loses everything up to the cap
+        assert!(annotation.moves[1].loss >= 900);
+        assert_eq!(annotation.moves[1].best_line[0].to.to_str(), "d8");
+        assert_eq!(annotation.moves[3].loss, 0);
+        assert!(annotation.moves[3].best_line.is_empty());
+
+        assert_eq!(annotation.white.moves, 2);
+        assert_eq!(annotation.white.blunders, 1);
+        assert_eq!(annotation.black.moves, 3);
+        assert_eq!(annotation.black.blunders, 2);
+        assert!(annotation.white.average_loss > 400.0);
+        assert!(annotation.black.accuracy < annotation.white.accuracy);
+
+        let pgn = game.to_pgn();
+        let text = pgn.replace('\n', " ");
+        assert!(text.contains("2. h3 $4 {Blunder. Rd8+ was best.} (2. Rd8+"));
+        assert!(text.contains("3. Rxd7 $1"));
+        // The annotated game reads back with the variations
+        let game = GameTree::from_pgn(&pgn).unwrap();
+        assert_eq!(game.main_line().len(), 5);
+
+        let game = GameTree::from_pgn(
+            "[FEN \"r7/4k1pp/8/1N6/8/8/6PP/6K1 b - - 0 1\"]\n\n1... Ke8 2. Nc7+ Kd7 3. Nxa8 *",
+        )
+        .unwrap();
+        let annotation = annotator.analyse(&game);
+        assert_eq!(annotation.moves[1].nag, Some(NAG_GOOD));
+        assert_eq!(annotation.player(Color::White).average_loss, 0.0);
+        assert!(annotation.white.accuracy > 99.0);
+        assert_eq!(annotation.black.blunders, 1);
+    }
+
+    #[test]
+    fn check_see() {
+        let see = |fen: &str, san: &str| {
+            let chess = Chess::from_fen(fen, false).unwrap();
+            chess.see(&chess.parse_san(san).unwrap())
+        };
+
+        // A rook for a defended pawn
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4RK2 w - - 0 1", "Rxe5"), -400);
+        // The rook behind makes taking back a loss for black
+        assert_eq!(see("4r1k1/8/8/4p3/8/8/4R3/4RK2 w - - 0 1", "Rxe5"), 100);
+        assert_eq!(see("4r1k1/8/8/4p3/8/8/4R3/5K2 w - - 0 1", "Rxe5"), -400);
+
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6"), 100);
+        assert_eq!(see("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6"), 0);
+
+        // Quiet moves only lose what can be taken
+        assert_eq!(see("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "Qd5"), -900);
+        assert_eq!(see("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "Qd5"), 0);
+        assert_eq!(see("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e8=Q"), 800);
+        assert_eq!(see("r3k3/8/8/8/8/8/8/R3K3 w Qq - 0 1", "O-O-O"), 0);
+
+        // The king can't take back while the bishop also covers the square
+        assert_eq!(see("4r1k1/1b6/8/8/4P3/3K4/8/8 b - - 0 1", "Rxe4"), 100);
+        assert_eq!(see("4r1k1/8/8/8/4P3/3K4/8/8 b - - 0 1", "Rxe4"), -400);
+    }
+
+    #[test]
+    fn check_threats() {
+        use chess::threats::*;
+
+        let chess = Chess::from_fen("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1", false).unwrap();
+        let hanging = hanging_pieces(&chess, Color::Black);
+        assert_eq!(hanging.len(), 1);
+        assert_eq!(hanging[0].square, Position::from_str("d5"));
+        assert_eq!(hanging[0].piece_type, PieceType::Knight);
+        assert_eq!(hanging[0].attackers, vec![Position::from_str("d1")]);
+        assert!(hanging_pieces(&chess, Color::White).is_empty());
+
+        // The queen is in danger even though it is not hanging
+        let chess = Chess::from_fen("4k3/8/3p4/4p3/3Q4/2P5/8/4K3 w - - 0 1", false).unwrap();
+        assert!(hanging_pieces(&chess, Color::White).is_empty());
+        let attacked = attacked_by_lesser(&chess, Color::White);
+        assert_eq!(attacked.len(), 1);
+        assert_eq!(attacked[0].square, Position::from_str("d4"));
+        assert_eq!(attacked[0].attackers, vec![Position::from_str("e5")]);
+
+        let map = AttackMap::new(chess.board());
+        assert_eq!(
+            map.attackers(Position::from_str("e5"), Color::White),
+            &[Position::from_str("d4")]
+        );
+        assert!(map.is_attacked(Position::from_str("e5"), Color::Black));
+        assert!(!map.is_attacked(Position::from_str("a1"), Color::Black));
+
+        // Moving the knight leaves the pawn to the other knight and puts itself next to a pawn
+        let chess = Chess::from_fen("4k3/8/2p2n2/8/4P3/2N5/8/4K3 w - - 0 1", false).unwrap();
+        let squares: Vec<String> = hanging_after(&chess, &chess.parse_san("Nb5").unwrap())
+            .iter()
+            .map(|p| p.square.to_str())
+            .collect();
+        assert_eq!(squares, vec!["e4", "b5"]);
+        assert!(hanging_after(&chess, &chess.parse_san("Kd2").unwrap()).is_empty());
+
+        let chess = Chess::from_fen("4r1k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1", false).unwrap();
+        let threats: Vec<String> = mate_threats(&chess).iter().map(|m| m.to_string()).collect();
+        assert_eq!(threats, vec!["e8e1"]);
+        let chess = Chess::from_fen("4r1k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", false).unwrap();
+        assert!(mate_threats(&chess).is_empty());
+        let chess = Chess::from_fen("6k1/5ppp/8/8/8/8/5PPP/4r1K1 w - - 0 1", false).unwrap();
+        assert!(mate_threats(&chess).is_empty());
+    }
+
+    #[test]
+    fn check_tactics() {
+        use chess::tactics::*;
+
+        let sq = Position::from_str;
+        let motifs = |fen: &str, san: &str| {
+            let chess = Chess::from_fen(fen, false).unwrap();
+            move_motifs(&chess, &chess.parse_san(san).unwrap())
+        };
+
+        assert_eq!(
+            motifs("r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1", "Nc7+"),
+            vec![Motif::Fork {
+                attacker: sq("c7"),
+                targets: vec![sq("a8"), sq("e8")]
+            }]
+        );
+        assert_eq!(
+            motifs("4k3/8/2n5/8/8/8/8/4KB2 w - - 0 1", "Bb5"),
+            vec![Motif::Pin {
+                attacker: sq("b5"),
+                pinned: sq("c6"),
+                behind: sq("e8"),
+                absolute: true
+            }]
+        );
+        assert_eq!(
+            motifs("4q1k1/8/2n5/8/8/8/8/5B1K w - - 0 1", "Bb5"),
+            vec![Motif::Pin {
+                attacker: sq("b5"),
+                pinned: sq("c6"),
+                behind: sq("e8"),
+                absolute: false
+            }]
+        );
+        assert_eq!(
+            motifs("8/8/4k2q/8/8/8/8/R3K3 w - - 0 1", "Ra6+"),
+            vec![Motif::Skewer {
+                attacker: sq("a6"),
+                front: sq("e6"),
+                behind: sq("h6")
+            }]
+        );
+
+        assert_eq!(
+            motifs("8/7k/8/8/8/3N4/8/1B2K3 w - - 0 1", "Nf4"),
+            vec![Motif::DiscoveredCheck {
+                attacker: sq("b1"),
+                king: sq("h7")
+            }]
+        );
+        assert_eq!(
+            motifs("8/8/6k1/8/8/3N4/8/1B2K3 w - - 0 1", "Ne5"),
+            vec![
+                Motif::DoubleCheck {
+                    king: sq("g6"),
+                    checkers: vec![sq("e5"), sq("b1")]
+                },
+                Motif::DiscoveredCheck {
+                    attacker: sq("b1"),
+                    king: sq("g6")
+                }
+            ]
+        );
+        assert_eq!(
+            motifs("7k/8/8/5q2/8/3N4/8/1B2K3 w - - 0 1", "Nc5"),
+            vec![Motif::DiscoveredAttack {
+                attacker: sq("b1"),
+                target: sq("f5")
+            }]
+        );
+        // The knight still blocks the bishop
+        assert!(motifs("7k/8/8/5q2/8/3N4/8/1B2K3 w - - 0 1", "Ke2").is_empty());
+
+        // Taking away the last free square in front of the king
+        assert_eq!(
+            motifs("6k1/5pp1/8/8/8/8/5PPP/3R1BK1 w - - 0 1", "Bd3"),
+            vec![Motif::BackRankWeakness { king: sq("g8") }]
+        );
+        let chess = Chess::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", false).unwrap();
+        assert_eq!(
+            position_motifs(&chess),
+            vec![Motif::BackRankWeakness { king: sq("g8") }]
+        );
+        // Without a black rook or queen the white king is safe
+        assert_eq!(back_rank_weakness(&chess, Color::White), None);
+
+        let chess = Chess::from_fen("4k3/8/2n5/1B6/8/8/8/4K3 b - - 0 1", false).unwrap();
+        assert_eq!(pins_and_skewers(&chess, Color::White).len(), 1);
+        assert!(pins_and_skewers(&chess, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn check_pawn_structure() {
+        use chess::eval::evaluate_terms;
+        use chess::pawns::PawnStructure;
+
+        let squares = |squares: &[Position]| squares.iter().map(|p| p.to_str()).collect::<Vec<_>>();
+        let chess =
+            Chess::from_fen("6k1/8/3p4/2p1p2P/2P1P3/1P2P3/P7/6K1 w - - 0 1", false).unwrap();
+
+        let white = PawnStructure::new(chess.board(), Color::White);
+        assert_eq!(squares(&white.isolated), vec!["e3", "e4", "h5"]);
+        assert_eq!(squares(&white.doubled), vec!["e3"]);
+        assert!(white.backward.is_empty());
+        assert_eq!(squares(&white.passed), vec!["a2", "h5"]);
+        let islands: Vec<Vec<String>> = white.islands.iter().map(|i| squares(i)).collect();
+        assert_eq!(
+            islands,
+            vec![vec!["a2", "b3", "c4"], vec!["e3", "e4"], vec!["h5"]]
+        );
+        assert_eq!(white.chains.len(), 1);
+        assert_eq!(squares(&white.chains[0]), vec!["a2", "b3", "c4"]);
+
+        let black = PawnStructure::new(chess.board(), Color::Black);
+        assert!(black.isolated.is_empty());
+        assert!(black.doubled.is_empty());
+        assert_eq!(squares(&black.backward), vec!["d6"]);
+        assert!(black.passed.is_empty());
+        assert_eq!(black.islands.len(), 1);
+        assert_eq!(squares(&black.chains[0]), vec!["d6", "c5", "e5"]);
+
+        // White: doubled -15, three isolated -36, passed on the second and fifth rank 5 + 35.
+        // Black: one backward pawn -8.
+        assert_eq!(evaluate_terms(&chess).pawn_structure, -3);
+
+        let chess = Chess::new();
+        let white = PawnStructure::new(chess.board(), Color::White);
+        assert_eq!(white.islands.len(), 1);
+        assert!(white.passed.is_empty() && white.isolated.is_empty() && white.backward.is_empty());
+        assert!(white.chains.is_empty());
+        assert_eq!(evaluate_terms(&chess).pawn_structure, 0);
+    }
 }